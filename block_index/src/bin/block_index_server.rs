@@ -0,0 +1,49 @@
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use block_index::*;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  block_index_server
+//  purpose - serve block_index json file over http, see BlockIndexServer for routes
+//  usage: block_index_server <block_index.json> [listen_addr]
+//  listen_addr defaults to 127.0.0.1:1984 (arweave node port, so tools can point to it as peer)
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+const USAGE: &str = "usage: block_index_server <block_index.json> [listen_addr]";
+const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:1984";
+
+fn run(path: &str, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let addr: SocketAddr = addr.parse().map_err(|e| format!("{}: {}", addr, e))?;
+    let mut index = BlockIndex3Json::new();
+    index.load_sync(path).map_err(|e| format!("{}: {}", path, e))?;
+    let server = Arc::new(BlockIndexServer::new(index)?);
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(async {
+        let (local_addr, fut) = server.serve(addr)?;
+        eprintln!("serving {} on http://{}", path, local_addr);
+        fut.await
+    })?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let arg_list: Vec<String> = std::env::args().skip(1).collect();
+    let arg_list: Vec<&str> = arg_list.iter().map(|v| v.as_str()).collect();
+    let (path, addr) = match arg_list.as_slice() {
+        [path] => (*path, DEFAULT_LISTEN_ADDR),
+        [path, addr] => (*path, *addr),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(path, addr) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
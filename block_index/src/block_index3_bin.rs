@@ -0,0 +1,307 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use data_encoding::BASE64URL_NOPAD;
+use memmap2::Mmap;
+
+use types::*;
+
+use crate::{block_location, BlockIndex3Json, BlockIndex3JsonEntity, BlockIndexError};
+use crate::reverse_map::ReverseMap;
+use crate::journal::write_atomic_with;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  BlockIndex3Bin
+//  purpose - fixed-width binary format, mmap'ed, no decode on load and no parse on lookup
+//  file layout: magic header, then one record per block, ordered by height (record 0 = genesis)
+//  record layout: indep_hash (48) | tx_root (32) | tx_root presence flag (1) | weave_size (16, i128 BE)
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub const BLOCK_INDEX3_BIN_MAGIC: [u8; 8] = *b"AWBI3B01";
+pub const BLOCK_INDEX3_BIN_HEADER_SIZE: usize = BLOCK_INDEX3_BIN_MAGIC.len();
+
+const WEAVE_SIZE_LENGTH: usize = 16;
+
+const REC_INDEP_HASH_OFFSET: usize = 0;
+const REC_TX_ROOT_OFFSET: usize = REC_INDEP_HASH_OFFSET + INDEPHASH_LENGTH;
+const REC_TX_ROOT_FLAG_OFFSET: usize = REC_TX_ROOT_OFFSET + TXROOT_LENGTH;
+const REC_WEAVE_SIZE_OFFSET: usize = REC_TX_ROOT_FLAG_OFFSET + 1;
+pub const BLOCK_INDEX3_BIN_RECORD_SIZE: usize = REC_WEAVE_SIZE_OFFSET + WEAVE_SIZE_LENGTH;
+
+pub(crate) fn encode_record(entity: &BlockIndexEntity, buf: &mut [u8; BLOCK_INDEX3_BIN_RECORD_SIZE]) {
+    buf[REC_INDEP_HASH_OFFSET..REC_TX_ROOT_OFFSET].copy_from_slice(&entity.indep_hash);
+    match entity.tx_root {
+        Some(tx_root) => {
+            buf[REC_TX_ROOT_OFFSET..REC_TX_ROOT_FLAG_OFFSET].copy_from_slice(&tx_root);
+            buf[REC_TX_ROOT_FLAG_OFFSET] = 1;
+        }
+        None => {
+            buf[REC_TX_ROOT_OFFSET..REC_TX_ROOT_FLAG_OFFSET].fill(0);
+            buf[REC_TX_ROOT_FLAG_OFFSET] = 0;
+        }
+    }
+    buf[REC_WEAVE_SIZE_OFFSET..].copy_from_slice(&entity.weave_size.to_be_bytes());
+}
+
+pub(crate) fn record_indep_hash(rec: &[u8]) -> IndepHashType {
+    rec[REC_INDEP_HASH_OFFSET..REC_TX_ROOT_OFFSET].try_into().unwrap()
+}
+
+pub(crate) fn record_tx_root(rec: &[u8]) -> Option<TxRootType> {
+    if rec[REC_TX_ROOT_FLAG_OFFSET] == 0 {
+        return None;
+    }
+    Some(rec[REC_TX_ROOT_OFFSET..REC_TX_ROOT_FLAG_OFFSET].try_into().unwrap())
+}
+
+pub(crate) fn record_weave_size(rec: &[u8]) -> WeaveSizeType {
+    WeaveSizeType::from_be_bytes(rec[REC_WEAVE_SIZE_OFFSET..].try_into().unwrap())
+}
+
+fn bin_format3_check(buf: &[u8]) -> Result<usize, BlockIndexError> {
+    if buf.len() < BLOCK_INDEX3_BIN_HEADER_SIZE || buf[..BLOCK_INDEX3_BIN_HEADER_SIZE] != BLOCK_INDEX3_BIN_MAGIC {
+        return Err(BlockIndexError::Corrupt("bad magic, not a BlockIndex3Bin file".into()));
+    }
+    let body_len = buf.len() - BLOCK_INDEX3_BIN_HEADER_SIZE;
+    if !body_len.is_multiple_of(BLOCK_INDEX3_BIN_RECORD_SIZE) {
+        return Err(BlockIndexError::Corrupt(format!("file body size {} is not multiple of record size {}", body_len, BLOCK_INDEX3_BIN_RECORD_SIZE)));
+    }
+    let count = body_len / BLOCK_INDEX3_BIN_RECORD_SIZE;
+    if count == 0 {
+        return Err(BlockIndexError::Corrupt("empty block index".into()));
+    }
+
+    let mut prev_weave_size: WeaveSizeType = 0;
+    for i in 0..count {
+        let start = BLOCK_INDEX3_BIN_HEADER_SIZE + i * BLOCK_INDEX3_BIN_RECORD_SIZE;
+        let rec = &buf[start..start + BLOCK_INDEX3_BIN_RECORD_SIZE];
+        let flag = rec[REC_TX_ROOT_FLAG_OFFSET];
+        if flag > 1 {
+            return Err(BlockIndexError::Corrupt(format!("record[{}] tx_root flag is {}, expected 0 or 1", i, flag)));
+        }
+        if flag == 0 && rec[REC_TX_ROOT_OFFSET..REC_TX_ROOT_FLAG_OFFSET].iter().any(|b| *b != 0) {
+            return Err(BlockIndexError::Corrupt(format!("record[{}] tx_root is absent but not zeroed", i)));
+        }
+        let weave_size = record_weave_size(rec);
+        if prev_weave_size > weave_size {
+            return Err(BlockIndexError::Corrupt(format!("record[{}] prev_weave_size > weave_size; {} > {}", i, prev_weave_size, weave_size)));
+        }
+        prev_weave_size = weave_size;
+    }
+    Ok(count)
+}
+
+pub struct BlockIndex3Bin {
+    mmap: Option<Mmap>,
+    count: usize,
+    chunk_offset_a: WeaveOffsetType,
+    chunk_offset_b: WeaveOffsetType,
+    // NOTE. only part which is not mapped, built by scan on load
+    reverse_map: ReverseMap,
+}
+impl Default for BlockIndex3Bin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl BlockIndex3Bin {
+    pub fn new() -> Self {
+        BlockIndex3Bin {
+            mmap: None,
+            count: 0,
+            chunk_offset_a: 0,
+            chunk_offset_b: 0,
+            reverse_map: ReverseMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    fn _record(&self, height: HeightType) -> Option<&[u8]> {
+        let idx = usize::try_from(height).ok()?;
+        if idx >= self.count {
+            return None;
+        }
+        let mmap = self.mmap.as_ref()?;
+        let start = BLOCK_INDEX3_BIN_HEADER_SIZE + idx * BLOCK_INDEX3_BIN_RECORD_SIZE;
+        Some(&mmap[start..start + BLOCK_INDEX3_BIN_RECORD_SIZE])
+    }
+
+    fn _prev_weave_size(&self, height: HeightType) -> WeaveSizeType {
+        if height == 0 {
+            return 0;
+        }
+        self._record(height - 1).map(record_weave_size).unwrap_or(0)
+    }
+
+    pub fn load_sync(&mut self, path: &str) -> Result<(), BlockIndexError> {
+        let file = File::open(path)?;
+        // NOTE. file must not be modified while mapped; we never write through the map
+        let mmap = unsafe { Mmap::map(&file)? };
+        let count = bin_format3_check(&mmap)?;
+
+        self.mmap = Some(mmap);
+        self.count = count;
+        self.chunk_offset_a = record_weave_size(self._record(0).unwrap());
+        self.chunk_offset_b = record_weave_size(self._record((count - 1) as HeightType).unwrap());
+        self.reverse_map = ReverseMap::with_capacity(count);
+        for height in 0..count as HeightType {
+            let rec = self._record(height).unwrap();
+            self.reverse_map.insert(height, record_indep_hash(rec), record_tx_root(rec));
+        }
+        Ok(())
+    }
+
+    // Never truncates path in place, it may be mapped (by this or other instance), see write_atomic_with
+    pub fn save_sync(&self, path: &str) -> Result<(), BlockIndexError> {
+        let mmap = self.mmap.as_ref().ok_or(BlockIndexError::NotLoaded)?;
+        write_atomic_with(Path::new(path), |file| Ok(file.write_all(mmap)?))
+    }
+
+    // Writes json block index to path in bin format, then maps it
+    pub fn from_json_sync(block_index: &BlockIndex3Json, path: &str) -> Result<Self, BlockIndexError> {
+        write_atomic_with(Path::new(path), |file| {
+            file.write_all(&BLOCK_INDEX3_BIN_MAGIC)?;
+            let mut buf = [0; BLOCK_INDEX3_BIN_RECORD_SIZE];
+            for (i, el) in block_index.block_list.iter().rev().enumerate() {
+                let entity = el.decode().ok_or_else(|| BlockIndexError::Corrupt(format!("Failed to decode block at height {}", i)))?;
+                encode_record(&entity, &mut buf);
+                file.write_all(&buf)?;
+            }
+            Ok(())
+        })?;
+
+        let mut ret = Self::new();
+        ret.load_sync(path)?;
+        Ok(ret)
+    }
+
+    pub fn to_json(&self) -> Result<BlockIndex3Json, BlockIndexError> {
+        let mut json = Vec::with_capacity(self.count);
+        for height in (0..self.count as HeightType).rev() {
+            let rec = self._record(height).unwrap();
+            json.push(BlockIndex3JsonEntity {
+                tx_root: record_tx_root(rec).map(|v| BASE64URL_NOPAD.encode(&v)).unwrap_or_default(),
+                weave_size: record_weave_size(rec).to_string(),
+                hash: BASE64URL_NOPAD.encode(&record_indep_hash(rec)),
+            });
+        }
+        let mut ret = BlockIndex3Json::new();
+        ret._load_from_original_format(json)?;
+        Ok(ret)
+    }
+
+    // lowest height with weave_size >= chunk_offset
+    fn _get_height_by_chunk_offset(&self, chunk_offset: WeaveOffsetType) -> Option<HeightType> {
+        if self.count == 0 || self.chunk_offset_a > chunk_offset || self.chunk_offset_b < chunk_offset {
+            return None;
+        }
+
+        let mut lo: usize = 0;
+        let mut hi: usize = self.count - 1;
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if record_weave_size(self._record(mid as HeightType)?) >= chunk_offset {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+        Some(lo as HeightType)
+    }
+
+    fn _get_record_by_chunk_offset(&self, chunk_offset: WeaveOffsetType) -> Option<&[u8]> {
+        self._record(self._get_height_by_chunk_offset(chunk_offset)?)
+    }
+}
+
+impl BlockIndex for BlockIndex3Bin {}
+impl BlockIndex3 for BlockIndex3Bin {
+    fn get_tip_height(&self) -> Option<HeightType> {
+        (self.count as HeightType).checked_sub(1)
+    }
+
+    fn get_by_height_full(&self, height: HeightType) -> Option<BlockIndexEntity> {
+        let rec = self._record(height)?;
+        let weave_size = record_weave_size(rec);
+        Some(BlockIndexEntity {
+            indep_hash: record_indep_hash(rec),
+            weave_size,
+            tx_root: record_tx_root(rec),
+            block_size: weave_size - self._prev_weave_size(height),
+        })
+    }
+
+    fn get_by_height_indep_hash(&self, height: HeightType) -> Option<IndepHashType> {
+        Some(record_indep_hash(self._record(height)?))
+    }
+
+    fn get_by_height_weave_size(&self, height: HeightType) -> Option<WeaveSizeType> {
+        Some(record_weave_size(self._record(height)?))
+    }
+
+    fn get_by_height_tx_root(&self, height: HeightType) -> Option<TxRootType> {
+        record_tx_root(self._record(height)?)
+    }
+
+    fn get_by_height_indep_hash_orig(&self, height: HeightType) -> Option<String> {
+        Some(BASE64URL_NOPAD.encode(&record_indep_hash(self._record(height)?)))
+    }
+
+    fn get_by_height_weave_size_orig(&self, height: HeightType) -> Option<String> {
+        Some(record_weave_size(self._record(height)?).to_string())
+    }
+
+    fn get_by_height_tx_root_orig(&self, height: HeightType) -> Option<String> {
+        Some(record_tx_root(self._record(height)?).map(|v| BASE64URL_NOPAD.encode(&v)).unwrap_or_default())
+    }
+
+    fn get_by_chunk_offset_full(&self, chunk_offset: WeaveOffsetType) -> Option<BlockIndexEntity> {
+        self.get_by_height_full(self._get_height_by_chunk_offset(chunk_offset)?)
+    }
+
+    fn get_by_chunk_offset_indep_hash(&self, chunk_offset: WeaveOffsetType) -> Option<IndepHashType> {
+        Some(record_indep_hash(self._get_record_by_chunk_offset(chunk_offset)?))
+    }
+
+    fn get_by_chunk_offset_weave_size(&self, chunk_offset: WeaveOffsetType) -> Option<WeaveSizeType> {
+        Some(record_weave_size(self._get_record_by_chunk_offset(chunk_offset)?))
+    }
+
+    fn get_by_chunk_offset_tx_root(&self, chunk_offset: WeaveOffsetType) -> Option<TxRootType> {
+        record_tx_root(self._get_record_by_chunk_offset(chunk_offset)?)
+    }
+
+    fn get_by_chunk_offset_indep_hash_orig(&self, chunk_offset: WeaveOffsetType) -> Option<String> {
+        self.get_by_height_indep_hash_orig(self._get_height_by_chunk_offset(chunk_offset)?)
+    }
+
+    fn get_by_chunk_offset_weave_size_orig(&self, chunk_offset: WeaveOffsetType) -> Option<String> {
+        self.get_by_height_weave_size_orig(self._get_height_by_chunk_offset(chunk_offset)?)
+    }
+
+    fn get_by_chunk_offset_tx_root_orig(&self, chunk_offset: WeaveOffsetType) -> Option<String> {
+        self.get_by_height_tx_root_orig(self._get_height_by_chunk_offset(chunk_offset)?)
+    }
+
+    fn locate_offset(&self, chunk_offset: WeaveOffsetType) -> Option<BlockLocation> {
+        let height = self._get_height_by_chunk_offset(chunk_offset)?;
+        Some(block_location(height, self.get_by_height_full(height)?))
+    }
+}
+
+impl BlockIndex3Reverse for BlockIndex3Bin {
+    fn get_height_by_indep_hash(&self, indep_hash: &IndepHashType) -> Option<HeightType> {
+        self.reverse_map.get_height_by_indep_hash(indep_hash)
+    }
+
+    fn get_heights_by_tx_root(&self, tx_root: &TxRootType) -> Vec<HeightType> {
+        self.reverse_map.get_heights_by_tx_root(tx_root)
+    }
+}
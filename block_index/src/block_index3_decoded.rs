@@ -0,0 +1,265 @@
+use std::fs::File;
+use std::io::Write;
+use data_encoding::BASE64URL_NOPAD;
+
+use types::*;
+
+use crate::{block_location, orig_format3_json_check, BlockIndex3Json, BlockIndex3JsonEntity, BlockIndexError};
+use crate::json_stream::BlockIndex3JsonStreamParser;
+use crate::reorg::{find_fork_height, orphaned_list, reorg_res, ReorgRes};
+use crate::reverse_map::ReverseMap;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  BlockIndex3Decoded
+//  purpose - decode everything once on load, lookups are plain array access
+//  columns are ordered by height (idx 0 = genesis)
+//  *_orig accessors re-encode on demand
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub struct BlockIndex3Decoded {
+    indep_hash_list: Vec<IndepHashType>,
+    tx_root_list: Vec<Option<TxRootType>>,
+    weave_size_list: Vec<WeaveSizeType>,
+    reverse_map: ReverseMap,
+}
+impl Default for BlockIndex3Decoded {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl BlockIndex3Decoded {
+    pub fn new() -> Self {
+        BlockIndex3Decoded {
+            indep_hash_list: Vec::new(),
+            tx_root_list: Vec::new(),
+            weave_size_list: Vec::new(),
+            reverse_map: ReverseMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.weave_size_list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.weave_size_list.is_empty()
+    }
+
+    fn _load_from_original_format(&mut self, json: &[BlockIndex3JsonEntity]) -> Result<(), BlockIndexError> {
+        orig_format3_json_check(json)?;
+
+        let mut indep_hash_list = Vec::with_capacity(json.len());
+        let mut tx_root_list = Vec::with_capacity(json.len());
+        let mut weave_size_list = Vec::with_capacity(json.len());
+        let mut reverse_map = ReverseMap::with_capacity(json.len());
+        for (i, el) in json.iter().rev().enumerate() {
+            let entity = el.decode().ok_or_else(|| BlockIndexError::Corrupt(format!("Failed to decode block at height {}", i)))?;
+            reverse_map.insert(i as HeightType, entity.indep_hash, entity.tx_root);
+            indep_hash_list.push(entity.indep_hash);
+            tx_root_list.push(entity.tx_root);
+            weave_size_list.push(entity.weave_size);
+        }
+
+        self.indep_hash_list = indep_hash_list;
+        self.tx_root_list = tx_root_list;
+        self.weave_size_list = weave_size_list;
+        self.reverse_map = reverse_map;
+        Ok(())
+    }
+
+    fn _to_original_format(&self) -> Vec<BlockIndex3JsonEntity> {
+        (0..self.len()).rev().map(|idx| {
+            BlockIndex3JsonEntity {
+                tx_root: self.tx_root_list[idx].map(|v| BASE64URL_NOPAD.encode(&v)).unwrap_or_default(),
+                weave_size: self.weave_size_list[idx].to_string(),
+                hash: BASE64URL_NOPAD.encode(&self.indep_hash_list[idx]),
+            }
+        }).collect()
+    }
+
+    pub fn from_json(block_index: &BlockIndex3Json) -> Result<Self, BlockIndexError> {
+        let mut ret = Self::new();
+        ret._load_from_original_format(&block_index.block_list)?;
+        Ok(ret)
+    }
+
+    pub fn to_json(&self) -> Result<BlockIndex3Json, BlockIndexError> {
+        let mut ret = BlockIndex3Json::new();
+        ret._load_from_original_format(self._to_original_format())?;
+        Ok(ret)
+    }
+
+    // WARNING impl is actually not async
+    pub async fn load(&mut self, path: &str) -> Result<(), BlockIndexError> {
+        self.load_sync(path)
+    }
+
+    pub fn save_sync(&self, path: &str) -> Result<(), BlockIndexError> {
+        let mut file = File::create(path)?;
+        let block_list = serde_json::to_string(&self._to_original_format())?;
+        file.write_all(block_list.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn load_sync(&mut self, path: &str) -> Result<(), BlockIndexError> {
+        let file = File::open(path)?;
+        let json = BlockIndex3JsonStreamParser::parse_reader(file)?;
+        self._load_from_original_format(&json)?;
+        Ok(())
+    }
+
+    // Rolls back local tail to fork height with other and applies other's branch on top
+    // No-op if other has nothing above fork height (other is behind or same)
+    // NOTE. does not decide which branch is better, caller does
+    pub fn reorg(&mut self, other: &dyn BlockIndex3) -> Result<ReorgRes, BlockIndexError> {
+        let fork_height = find_fork_height(self, other).ok_or(BlockIndexError::NoCommonGenesis)?;
+        let fork_idx = fork_height as usize;
+        let fork_weave_size = self.weave_size_list[fork_idx];
+        // find_fork_height found common block, other is not empty
+        let other_tip = other.get_tip_height().unwrap();
+
+        // oldest first
+        let mut new_entity_list = Vec::with_capacity((other_tip - fork_height) as usize);
+        let mut prev_weave_size = fork_weave_size;
+        for height in fork_height + 1..=other_tip {
+            let entity = other.get_by_height_full(height)
+                .ok_or_else(|| BlockIndexError::Corrupt(format!("other block index has no block at height {}", height)))?;
+            if prev_weave_size > entity.weave_size {
+                return Err(BlockIndexError::Corrupt(format!("height {} prev_weave_size > weave_size; {} > {}", height, prev_weave_size, entity.weave_size)));
+            }
+            prev_weave_size = entity.weave_size;
+            new_entity_list.push(entity);
+        }
+        if new_entity_list.is_empty() {
+            return Ok(reorg_res(fork_height, Vec::new(), fork_weave_size, 0));
+        }
+        let applied_count = new_entity_list.len();
+        let orphaned_list = orphaned_list(self, fork_height);
+
+        self.indep_hash_list.truncate(fork_idx + 1);
+        self.tx_root_list.truncate(fork_idx + 1);
+        self.weave_size_list.truncate(fork_idx + 1);
+        for orphaned in &orphaned_list {
+            self.reverse_map.remove(orphaned.height, &orphaned.indep_hash, orphaned.tx_root.as_ref());
+        }
+        for entity in new_entity_list {
+            self.reverse_map.insert(self.len() as HeightType, entity.indep_hash, entity.tx_root);
+            self.indep_hash_list.push(entity.indep_hash);
+            self.tx_root_list.push(entity.tx_root);
+            self.weave_size_list.push(entity.weave_size);
+        }
+
+        Ok(reorg_res(fork_height, orphaned_list, fork_weave_size, applied_count))
+    }
+
+    fn _idx_by_height(&self, height: HeightType) -> Option<usize> {
+        let idx = usize::try_from(height).ok()?;
+        if idx >= self.len() {
+            return None;
+        }
+        Some(idx)
+    }
+
+    // lowest height with weave_size >= chunk_offset
+    fn _idx_by_chunk_offset(&self, chunk_offset: WeaveOffsetType) -> Option<usize> {
+        let first = *self.weave_size_list.first()?;
+        let last = *self.weave_size_list.last()?;
+        if first > chunk_offset || last < chunk_offset {
+            return None;
+        }
+        Some(self.weave_size_list.partition_point(|weave_size| *weave_size < chunk_offset))
+    }
+
+    fn _full_by_idx(&self, idx: usize) -> BlockIndexEntity {
+        let weave_size = self.weave_size_list[idx];
+        let prev_weave_size = if idx == 0 { 0 } else { self.weave_size_list[idx - 1] };
+        BlockIndexEntity {
+            indep_hash: self.indep_hash_list[idx],
+            weave_size,
+            tx_root: self.tx_root_list[idx],
+            block_size: weave_size - prev_weave_size,
+        }
+    }
+
+    fn _tx_root_orig_by_idx(&self, idx: usize) -> String {
+        self.tx_root_list[idx].map(|v| BASE64URL_NOPAD.encode(&v)).unwrap_or_default()
+    }
+}
+
+impl BlockIndex for BlockIndex3Decoded {}
+impl BlockIndex3 for BlockIndex3Decoded {
+    fn get_tip_height(&self) -> Option<HeightType> {
+        (self.len() as HeightType).checked_sub(1)
+    }
+
+    fn get_by_height_full(&self, height: HeightType) -> Option<BlockIndexEntity> {
+        Some(self._full_by_idx(self._idx_by_height(height)?))
+    }
+
+    fn get_by_height_indep_hash(&self, height: HeightType) -> Option<IndepHashType> {
+        Some(self.indep_hash_list[self._idx_by_height(height)?])
+    }
+
+    fn get_by_height_weave_size(&self, height: HeightType) -> Option<WeaveSizeType> {
+        Some(self.weave_size_list[self._idx_by_height(height)?])
+    }
+
+    fn get_by_height_tx_root(&self, height: HeightType) -> Option<TxRootType> {
+        self.tx_root_list[self._idx_by_height(height)?]
+    }
+
+    fn get_by_height_indep_hash_orig(&self, height: HeightType) -> Option<String> {
+        Some(BASE64URL_NOPAD.encode(&self.indep_hash_list[self._idx_by_height(height)?]))
+    }
+
+    fn get_by_height_weave_size_orig(&self, height: HeightType) -> Option<String> {
+        Some(self.weave_size_list[self._idx_by_height(height)?].to_string())
+    }
+
+    fn get_by_height_tx_root_orig(&self, height: HeightType) -> Option<String> {
+        Some(self._tx_root_orig_by_idx(self._idx_by_height(height)?))
+    }
+
+    fn get_by_chunk_offset_full(&self, chunk_offset: WeaveOffsetType) -> Option<BlockIndexEntity> {
+        Some(self._full_by_idx(self._idx_by_chunk_offset(chunk_offset)?))
+    }
+
+    fn get_by_chunk_offset_indep_hash(&self, chunk_offset: WeaveOffsetType) -> Option<IndepHashType> {
+        Some(self.indep_hash_list[self._idx_by_chunk_offset(chunk_offset)?])
+    }
+
+    fn get_by_chunk_offset_weave_size(&self, chunk_offset: WeaveOffsetType) -> Option<WeaveSizeType> {
+        Some(self.weave_size_list[self._idx_by_chunk_offset(chunk_offset)?])
+    }
+
+    fn get_by_chunk_offset_tx_root(&self, chunk_offset: WeaveOffsetType) -> Option<TxRootType> {
+        self.tx_root_list[self._idx_by_chunk_offset(chunk_offset)?]
+    }
+
+    fn get_by_chunk_offset_indep_hash_orig(&self, chunk_offset: WeaveOffsetType) -> Option<String> {
+        Some(BASE64URL_NOPAD.encode(&self.indep_hash_list[self._idx_by_chunk_offset(chunk_offset)?]))
+    }
+
+    fn get_by_chunk_offset_weave_size_orig(&self, chunk_offset: WeaveOffsetType) -> Option<String> {
+        Some(self.weave_size_list[self._idx_by_chunk_offset(chunk_offset)?].to_string())
+    }
+
+    fn get_by_chunk_offset_tx_root_orig(&self, chunk_offset: WeaveOffsetType) -> Option<String> {
+        Some(self._tx_root_orig_by_idx(self._idx_by_chunk_offset(chunk_offset)?))
+    }
+
+    fn locate_offset(&self, chunk_offset: WeaveOffsetType) -> Option<BlockLocation> {
+        let idx = self._idx_by_chunk_offset(chunk_offset)?;
+        Some(block_location(idx as HeightType, self._full_by_idx(idx)))
+    }
+}
+
+impl BlockIndex3Reverse for BlockIndex3Decoded {
+    fn get_height_by_indep_hash(&self, indep_hash: &IndepHashType) -> Option<HeightType> {
+        self.reverse_map.get_height_by_indep_hash(indep_hash)
+    }
+
+    fn get_heights_by_tx_root(&self, tx_root: &TxRootType) -> Vec<HeightType> {
+        self.reverse_map.get_heights_by_tx_root(tx_root)
+    }
+}
@@ -0,0 +1,268 @@
+use data_encoding::BASE64URL_NOPAD;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use types::*;
+
+use crate::{block_location, BlockIndex3Json, BlockIndex3JsonEntity, BlockIndexError};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  BlockIndex3Sqlite
+//  purpose - block index shared between processes and stored next to other metadata
+//  one row per block, nothing is cached in memory, every lookup is a query
+//  weave_size is i128, stored as 16 bytes BE blob; weave_size is never negative,
+//  so blob order (memcmp) is numeric order and index on weave_size works for offset lookup
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS block_index3 (
+    height INTEGER PRIMARY KEY NOT NULL,
+    indep_hash BLOB NOT NULL,
+    tx_root BLOB,
+    weave_size BLOB NOT NULL
+);
+CREATE INDEX IF NOT EXISTS block_index3_weave_size ON block_index3 (weave_size);
+CREATE INDEX IF NOT EXISTS block_index3_indep_hash ON block_index3 (indep_hash);
+CREATE INDEX IF NOT EXISTS block_index3_tx_root ON block_index3 (tx_root);
+";
+
+struct SqliteRow {
+    indep_hash: IndepHashType,
+    tx_root: Option<TxRootType>,
+    weave_size: WeaveSizeType,
+}
+
+fn weave_size_to_sql(weave_size: WeaveSizeType) -> [u8; 16] {
+    weave_size.to_be_bytes()
+}
+
+fn weave_size_from_sql(buf: &[u8]) -> Option<WeaveSizeType> {
+    Some(WeaveSizeType::from_be_bytes(buf.try_into().ok()?))
+}
+
+pub struct BlockIndex3Sqlite {
+    conn: Connection,
+}
+impl BlockIndex3Sqlite {
+    // Creates table and indexes if file is new
+    pub fn open(path: &str) -> Result<Self, BlockIndexError> {
+        let conn = Connection::open(path)?;
+        // readers in other processes do not block on writer
+        conn.query_row("PRAGMA journal_mode=WAL", [], |_| Ok(()))?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        Self::_init(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self, BlockIndexError> {
+        Self::_init(Connection::open_in_memory()?)
+    }
+
+    fn _init(conn: Connection) -> Result<Self, BlockIndexError> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(BlockIndex3Sqlite { conn })
+    }
+
+    // Err on locked or corrupt db, not reported as empty index
+    pub fn len(&self) -> Result<usize, BlockIndexError> {
+        let count = self.conn.query_row("SELECT COUNT(*) FROM block_index3", [], |row| row.get::<_, i64>(0))?;
+        Ok(count as usize)
+    }
+
+    pub fn is_empty(&self) -> Result<bool, BlockIndexError> {
+        Ok(self.len()? == 0)
+    }
+
+    // Replaces whole table with json block index in one transaction
+    pub fn import_json(&mut self, block_index: &BlockIndex3Json) -> Result<(), BlockIndexError> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM block_index3", [])?;
+        {
+            let mut stmt = tx.prepare("INSERT INTO block_index3 (height, indep_hash, tx_root, weave_size) VALUES (?1, ?2, ?3, ?4)")?;
+            for (height, el) in block_index.block_list.iter().rev().enumerate() {
+                let entity = el.decode().ok_or_else(|| BlockIndexError::Corrupt(format!("Failed to decode block at height {}", height)))?;
+                stmt.execute(params![
+                    height as i64,
+                    &entity.indep_hash[..],
+                    entity.tx_root.as_ref().map(|v| &v[..]),
+                    &weave_size_to_sql(entity.weave_size)[..],
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // Opens (creates) db at path and imports json block index there
+    pub fn from_json_sync(block_index: &BlockIndex3Json, path: &str) -> Result<Self, BlockIndexError> {
+        let mut ret = Self::open(path)?;
+        ret.import_json(block_index)?;
+        Ok(ret)
+    }
+
+    pub fn to_json(&self) -> Result<BlockIndex3Json, BlockIndexError> {
+        let mut stmt = self.conn.prepare("SELECT indep_hash, tx_root, weave_size FROM block_index3 ORDER BY height DESC")?;
+        let mut rows = stmt.query([])?;
+        let mut json = Vec::new();
+        while let Some(row) = rows.next()? {
+            let row = Self::_decode_row(row).ok_or_else(|| BlockIndexError::Corrupt(format!("bad row json[{}]", json.len())))?;
+            json.push(BlockIndex3JsonEntity {
+                tx_root: row.tx_root.map(|v| BASE64URL_NOPAD.encode(&v)).unwrap_or_default(),
+                weave_size: row.weave_size.to_string(),
+                hash: BASE64URL_NOPAD.encode(&row.indep_hash),
+            });
+        }
+        let mut ret = BlockIndex3Json::new();
+        ret._load_from_original_format(json)?;
+        Ok(ret)
+    }
+
+    // expects columns indep_hash, tx_root, weave_size
+    fn _decode_row(row: &rusqlite::Row) -> Option<SqliteRow> {
+        let indep_hash: Vec<u8> = row.get(0).ok()?;
+        let tx_root: Option<Vec<u8>> = row.get(1).ok()?;
+        let weave_size: Vec<u8> = row.get(2).ok()?;
+        let tx_root = match tx_root {
+            Some(tx_root) => Some(tx_root.try_into().ok()?),
+            None => None,
+        };
+        Some(SqliteRow {
+            indep_hash: indep_hash.try_into().ok()?,
+            tx_root,
+            weave_size: weave_size_from_sql(&weave_size)?,
+        })
+    }
+
+    fn _row_by_height(&self, height: HeightType) -> Option<SqliteRow> {
+        let height = i64::try_from(height).ok()?;
+        self.conn.query_row(
+            "SELECT indep_hash, tx_root, weave_size FROM block_index3 WHERE height = ?1",
+            [height],
+            |row| Ok(Self::_decode_row(row)),
+        ).optional().ok()??
+    }
+
+    fn _prev_weave_size(&self, height: HeightType) -> Option<WeaveSizeType> {
+        if height == 0 {
+            return Some(0);
+        }
+        Some(self._row_by_height(height - 1)?.weave_size)
+    }
+
+    // lowest height with weave_size >= chunk_offset
+    fn _height_by_chunk_offset(&self, chunk_offset: WeaveOffsetType) -> Option<HeightType> {
+        if chunk_offset < 0 {
+            return None;
+        }
+        let (height, weave_size): (i64, Vec<u8>) = self.conn.query_row(
+            "SELECT height, weave_size FROM block_index3 WHERE weave_size >= ?1 ORDER BY weave_size, height LIMIT 1",
+            [&weave_size_to_sql(chunk_offset)[..]],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional().ok()??;
+        // below genesis weave_size
+        if height == 0 && weave_size_from_sql(&weave_size)? != chunk_offset {
+            return None;
+        }
+        HeightType::try_from(height).ok()
+    }
+
+    fn _full(&self, height: HeightType) -> Option<BlockIndexEntity> {
+        let row = self._row_by_height(height)?;
+        let prev_weave_size = self._prev_weave_size(height)?;
+        Some(BlockIndexEntity {
+            indep_hash: row.indep_hash,
+            weave_size: row.weave_size,
+            tx_root: row.tx_root,
+            block_size: row.weave_size - prev_weave_size,
+        })
+    }
+}
+
+impl BlockIndex for BlockIndex3Sqlite {}
+impl BlockIndex3 for BlockIndex3Sqlite {
+    fn get_tip_height(&self) -> Option<HeightType> {
+        let height: Option<i64> = self.conn.query_row("SELECT MAX(height) FROM block_index3", [], |row| row.get(0)).ok()?;
+        HeightType::try_from(height?).ok()
+    }
+
+    fn get_by_height_full(&self, height: HeightType) -> Option<BlockIndexEntity> {
+        self._full(height)
+    }
+
+    fn get_by_height_indep_hash(&self, height: HeightType) -> Option<IndepHashType> {
+        Some(self._row_by_height(height)?.indep_hash)
+    }
+
+    fn get_by_height_weave_size(&self, height: HeightType) -> Option<WeaveSizeType> {
+        Some(self._row_by_height(height)?.weave_size)
+    }
+
+    fn get_by_height_tx_root(&self, height: HeightType) -> Option<TxRootType> {
+        self._row_by_height(height)?.tx_root
+    }
+
+    fn get_by_height_indep_hash_orig(&self, height: HeightType) -> Option<String> {
+        Some(BASE64URL_NOPAD.encode(&self._row_by_height(height)?.indep_hash))
+    }
+
+    fn get_by_height_weave_size_orig(&self, height: HeightType) -> Option<String> {
+        Some(self._row_by_height(height)?.weave_size.to_string())
+    }
+
+    fn get_by_height_tx_root_orig(&self, height: HeightType) -> Option<String> {
+        Some(self._row_by_height(height)?.tx_root.map(|v| BASE64URL_NOPAD.encode(&v)).unwrap_or_default())
+    }
+
+    fn get_by_chunk_offset_full(&self, chunk_offset: WeaveOffsetType) -> Option<BlockIndexEntity> {
+        self._full(self._height_by_chunk_offset(chunk_offset)?)
+    }
+
+    fn get_by_chunk_offset_indep_hash(&self, chunk_offset: WeaveOffsetType) -> Option<IndepHashType> {
+        self.get_by_height_indep_hash(self._height_by_chunk_offset(chunk_offset)?)
+    }
+
+    fn get_by_chunk_offset_weave_size(&self, chunk_offset: WeaveOffsetType) -> Option<WeaveSizeType> {
+        self.get_by_height_weave_size(self._height_by_chunk_offset(chunk_offset)?)
+    }
+
+    fn get_by_chunk_offset_tx_root(&self, chunk_offset: WeaveOffsetType) -> Option<TxRootType> {
+        self.get_by_height_tx_root(self._height_by_chunk_offset(chunk_offset)?)
+    }
+
+    fn get_by_chunk_offset_indep_hash_orig(&self, chunk_offset: WeaveOffsetType) -> Option<String> {
+        self.get_by_height_indep_hash_orig(self._height_by_chunk_offset(chunk_offset)?)
+    }
+
+    fn get_by_chunk_offset_weave_size_orig(&self, chunk_offset: WeaveOffsetType) -> Option<String> {
+        self.get_by_height_weave_size_orig(self._height_by_chunk_offset(chunk_offset)?)
+    }
+
+    fn get_by_chunk_offset_tx_root_orig(&self, chunk_offset: WeaveOffsetType) -> Option<String> {
+        self.get_by_height_tx_root_orig(self._height_by_chunk_offset(chunk_offset)?)
+    }
+
+    fn locate_offset(&self, chunk_offset: WeaveOffsetType) -> Option<BlockLocation> {
+        let height = self._height_by_chunk_offset(chunk_offset)?;
+        Some(block_location(height, self._full(height)?))
+    }
+}
+
+impl BlockIndex3Reverse for BlockIndex3Sqlite {
+    fn get_height_by_indep_hash(&self, indep_hash: &IndepHashType) -> Option<HeightType> {
+        // same as ReverseMap, highest height wins on duplicates
+        let height: i64 = self.conn.query_row(
+            "SELECT height FROM block_index3 WHERE indep_hash = ?1 ORDER BY height DESC LIMIT 1",
+            [&indep_hash[..]],
+            |row| row.get(0),
+        ).optional().ok()??;
+        HeightType::try_from(height).ok()
+    }
+
+    fn get_heights_by_tx_root(&self, tx_root: &TxRootType) -> Vec<HeightType> {
+        let query = || -> rusqlite::Result<Vec<HeightType>> {
+            let mut stmt = self.conn.prepare("SELECT height FROM block_index3 WHERE tx_root = ?1 ORDER BY height")?;
+            let height_list = stmt.query_map([&tx_root[..]], |row| row.get::<_, i64>(0))?
+                .collect::<rusqlite::Result<Vec<i64>>>()?;
+            Ok(height_list.into_iter().map(|v| v as HeightType).collect())
+        };
+        query().unwrap_or_default()
+    }
+}
@@ -0,0 +1,139 @@
+use std::fs::File;
+use serde::Deserialize;
+use data_encoding::BASE64URL_NOPAD;
+
+use types::*;
+
+use crate::{BlockIndex3JsonEntity, BlockIndexError};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  Checkpoints
+//  purpose - known-good height -> indep_hash, weave_size to check downloaded index against
+//  file is json list of {"height": 0, "indep_hash": "<base64url>", "weave_size": "<decimal>"}
+//  (same string encoding as /block_index)
+//  no list is embedded, checkpoints must come from source independent of peers being checked
+//  checkpoints above index tip can not be checked and are skipped
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Checkpoint {
+    pub height: HeightType,
+    pub indep_hash: IndepHashType,
+    pub weave_size: WeaveSizeType,
+}
+impl Checkpoint {
+    fn _check(&self, indep_hash: &IndepHashType, weave_size: WeaveSizeType) -> Result<(), BlockIndexError> {
+        if *indep_hash != self.indep_hash {
+            return Err(BlockIndexError::CheckpointMismatch { height: self.height, field: "indep_hash" });
+        }
+        if weave_size != self.weave_size {
+            return Err(BlockIndexError::CheckpointMismatch { height: self.height, field: "weave_size" });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct CheckpointJson {
+    height: HeightType,
+    indep_hash: String,
+    weave_size: String,
+}
+
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct CheckpointList {
+    // ascending by height, heights are unique
+    list: Vec<Checkpoint>,
+}
+impl CheckpointList {
+    pub fn new() -> Self {
+        CheckpointList {
+            list: Vec::new(),
+        }
+    }
+
+    // Sorts by height, same checkpoint twice is fine, conflicting ones are not
+    pub fn from_list(mut list: Vec<Checkpoint>) -> Result<Self, BlockIndexError> {
+        list.sort_by_key(|v| v.height);
+        list.dedup();
+        for pair in list.windows(2) {
+            if pair[0].height == pair[1].height {
+                return Err(BlockIndexError::InvalidArgument(format!("conflicting checkpoints at height {}", pair[0].height)));
+            }
+        }
+        Ok(CheckpointList { list })
+    }
+
+    pub fn from_json_str(json: &str) -> Result<Self, BlockIndexError> {
+        Self::_from_json(serde_json::from_str(json)?)
+    }
+
+    pub fn load_sync(path: &str) -> Result<Self, BlockIndexError> {
+        Self::_from_json(serde_json::from_reader(std::io::BufReader::new(File::open(path)?))?)
+    }
+
+    fn _from_json(json: Vec<CheckpointJson>) -> Result<Self, BlockIndexError> {
+        let mut list = Vec::with_capacity(json.len());
+        for (idx, el) in json.into_iter().enumerate() {
+            let mut indep_hash: IndepHashType = [0; INDEPHASH_LENGTH];
+            let bad_base64 = || BlockIndexError::BadBase64 { idx, field: "indep_hash", len: 64 };
+            // decode_mut panics on length mismatch
+            if BASE64URL_NOPAD.decode_len(el.indep_hash.len()).ok() != Some(INDEPHASH_LENGTH) {
+                return Err(bad_base64());
+            }
+            BASE64URL_NOPAD.decode_mut(el.indep_hash.as_bytes(), &mut indep_hash).map_err(|_| bad_base64())?;
+            // parse alone accepts leading +
+            if el.weave_size.is_empty() || !el.weave_size.bytes().all(|c| c.is_ascii_digit()) {
+                return Err(BlockIndexError::BadWeaveSize { idx });
+            }
+            let weave_size = el.weave_size.parse().map_err(|_| BlockIndexError::BadWeaveSize { idx })?;
+            list.push(Checkpoint { height: el.height, indep_hash, weave_size });
+        }
+        Self::from_list(list)
+    }
+
+    pub fn list(&self) -> &[Checkpoint] {
+        &self.list
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    // block_list is newest first, its last entry is block at base_height
+    // Returns count of checked checkpoints
+    pub(crate) fn verify_block_list(&self, block_list: &[BlockIndex3JsonEntity], base_height: HeightType) -> Result<usize, BlockIndexError> {
+        let end_height = base_height + block_list.len() as HeightType;
+        let mut count = 0;
+        for checkpoint in self.list.iter().filter(|v| v.height >= base_height && v.height < end_height) {
+            let idx = (end_height - 1 - checkpoint.height) as usize;
+            let entity = block_list[idx].decode()
+                .ok_or_else(|| BlockIndexError::Corrupt(format!("Failed to decode block at height {}", checkpoint.height)))?;
+            checkpoint._check(&entity.indep_hash, entity.weave_size)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+// Checks every checkpoint at or below index tip
+// Returns count of checked checkpoints, CheckpointMismatch on first contradiction
+pub fn verify_against_checkpoints(index: &dyn BlockIndex3, checkpoint_list: &CheckpointList) -> Result<usize, BlockIndexError> {
+    let tip_height = match index.get_tip_height() {
+        Some(tip_height) => tip_height,
+        None => return Ok(0),
+    };
+    let mut count = 0;
+    for checkpoint in checkpoint_list.list.iter().take_while(|v| v.height <= tip_height) {
+        let err = || BlockIndexError::Corrupt(format!("block index has no block at height {}", checkpoint.height));
+        let indep_hash = index.get_by_height_indep_hash(checkpoint.height).ok_or_else(err)?;
+        let weave_size = index.get_by_height_weave_size(checkpoint.height).ok_or_else(err)?;
+        checkpoint._check(&indep_hash, weave_size)?;
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,216 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+use futures::future::join_all;
+
+use types::*;
+
+use crate::{fetch_block_index_json, BlockIndex3Json, BlockIndex3JsonEntity, BlockIndexError};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  Consensus download
+//  purpose - do not trust single peer, fetch /block_index from all peers concurrently
+//  and accept index only if quorum of peers has same indep_hash at sampled heights
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub struct ConsensusConfig {
+    // min count of peers agreeing on every sampled height
+    pub quorum: usize,
+    // heights are sampled evenly from 0 to agreed tip, agreed tip is always sampled
+    pub sample_count: usize,
+    // whole /block_index request to one peer
+    pub timeout: Duration,
+}
+impl Default for ConsensusConfig {
+    fn default() -> Self {
+        ConsensusConfig {
+            quorum: 2,
+            sample_count: 32,
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum ConsensusPeerStatus {
+    Agreed,
+    // first sampled height where peer differs from majority
+    Disagreed { height: HeightType },
+    // peer tip is below agreed tip, not counted
+    Lagging { tip_height: HeightType },
+    Failed { err: String },
+}
+
+#[derive(PartialEq, Debug)]
+pub struct ConsensusPeerRes {
+    pub peer_url: String,
+    pub status: ConsensusPeerStatus,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct ConsensusRes {
+    // accepted index is truncated to this height
+    pub tip_height: HeightType,
+    pub sample_height_list: Vec<HeightType>,
+    // same order as peer_url_list
+    pub peer_res_list: Vec<ConsensusPeerRes>,
+}
+
+// Returned as BlockIndexError::NoQuorum when quorum is not reached, has per peer statuses
+// Agreed there means peer is in the largest group
+#[derive(Debug)]
+pub struct ConsensusError {
+    pub quorum: usize,
+    pub peer_res_list: Vec<ConsensusPeerRes>,
+}
+impl fmt::Display for ConsensusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "block index quorum {} not reached;", self.quorum)?;
+        for peer_res in &self.peer_res_list {
+            write!(f, " {}: {:?};", peer_res.peer_url, peer_res.status)?;
+        }
+        Ok(())
+    }
+}
+impl Error for ConsensusError {}
+
+fn sample_height_list(tip_height: HeightType, sample_count: usize) -> Vec<HeightType> {
+    let sample_count = sample_count as HeightType;
+    let mut ret: Vec<HeightType> = (0..sample_count)
+        .map(|i| if sample_count > 1 { tip_height * i / (sample_count - 1) } else { 0 })
+        .collect();
+    ret.push(tip_height);
+    ret.sort_unstable();
+    ret.dedup();
+    ret
+}
+
+// block_list is newest first
+fn hash_at_height(block_list: &[BlockIndex3JsonEntity], height: HeightType) -> &str {
+    &block_list[block_list.len() - 1 - height as usize].hash
+}
+
+async fn fetch_checked_block_index_json(client: &reqwest::Client, peer_url: &str) -> Result<Vec<BlockIndex3JsonEntity>, BlockIndexError> {
+    let url = format!("{}/block_index", peer_url);
+    let json = fetch_block_index_json(client, &url).await?.ok_or_else(|| BlockIndexError::http(&url, "is not available"))?;
+    if json.is_empty() {
+        return Err(BlockIndexError::http(&url, "is empty"));
+    }
+    Ok(json)
+}
+
+impl BlockIndex3Json {
+    // Downloads /block_index from every peer concurrently
+    // Accepted index is one agreed by config.quorum peers and by strictly more peers than any other group,
+    // truncated to highest height quorum peers have
+    pub async fn download_consensus(&mut self, peer_url_list: &[String], config: &ConsensusConfig) -> Result<ConsensusRes, BlockIndexError> {
+        if config.quorum == 0 {
+            return Err(BlockIndexError::InvalidArgument("quorum must be > 0".into()));
+        }
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| BlockIndexError::http("", e))?;
+
+        let fetch_list = join_all(peer_url_list.iter().map(|peer_url| fetch_checked_block_index_json(&client, peer_url))).await;
+
+        let mut peer_status_list: Vec<Option<ConsensusPeerStatus>> = Vec::with_capacity(fetch_list.len());
+        let mut json_list: Vec<Option<Vec<BlockIndex3JsonEntity>>> = Vec::with_capacity(fetch_list.len());
+        for fetch in fetch_list {
+            match fetch {
+                Ok(json) => {
+                    peer_status_list.push(None);
+                    json_list.push(Some(json));
+                }
+                Err(err) => {
+                    peer_status_list.push(Some(ConsensusPeerStatus::Failed { err: err.to_string() }));
+                    json_list.push(None);
+                }
+            }
+        }
+        let to_peer_res_list = |peer_status_list: Vec<Option<ConsensusPeerStatus>>| -> Vec<ConsensusPeerRes> {
+            peer_url_list.iter().zip(peer_status_list).map(|(peer_url, status)| ConsensusPeerRes {
+                peer_url: peer_url.clone(),
+                status: status.unwrap_or(ConsensusPeerStatus::Agreed),
+            }).collect()
+        };
+
+        // highest height at least quorum peers have
+        let mut tip_height_list: Vec<HeightType> = json_list.iter().flatten().map(|json| (json.len() - 1) as HeightType).collect();
+        tip_height_list.sort_unstable_by(|a, b| b.cmp(a));
+        let tip_height = match tip_height_list.get(config.quorum - 1) {
+            Some(tip_height) => *tip_height,
+            None => return Err(BlockIndexError::NoQuorum(ConsensusError {
+                quorum: config.quorum,
+                peer_res_list: to_peer_res_list(peer_status_list),
+            })),
+        };
+        let sample_height_list = sample_height_list(tip_height, config.sample_count);
+
+        // peers with same hashes on all sample heights
+        let mut group_list: Vec<(Vec<&str>, Vec<usize>)> = Vec::new();
+        for (peer_idx, json) in json_list.iter().enumerate() {
+            let json = match json {
+                Some(json) => json,
+                None => continue,
+            };
+            let peer_tip_height = (json.len() - 1) as HeightType;
+            if peer_tip_height < tip_height {
+                peer_status_list[peer_idx] = Some(ConsensusPeerStatus::Lagging { tip_height: peer_tip_height });
+                continue;
+            }
+            let key: Vec<&str> = sample_height_list.iter().map(|height| hash_at_height(json, *height)).collect();
+            match group_list.iter_mut().find(|(group_key, _)| *group_key == key) {
+                Some((_, peer_idx_list)) => peer_idx_list.push(peer_idx),
+                None => group_list.push((key, vec![peer_idx])),
+            }
+        }
+
+        // not empty, at least quorum peers reach tip_height
+        let best_group_idx = (0..group_list.len()).max_by(|a, b| {
+            // earliest group is reported on tie, tie itself is no quorum below
+            group_list[*a].1.len().cmp(&group_list[*b].1.len()).then(b.cmp(a))
+        }).unwrap();
+        let best_group_len = group_list[best_group_idx].1.len();
+        let is_tie = group_list.iter().enumerate().any(|(idx, (_, peer_idx_list))| idx != best_group_idx && peer_idx_list.len() == best_group_len);
+        let peer_status_list = Self::_consensus_disagreed(peer_status_list, &group_list, best_group_idx, &sample_height_list);
+        if best_group_len < config.quorum || is_tie {
+            return Err(BlockIndexError::NoQuorum(ConsensusError {
+                quorum: config.quorum,
+                peer_res_list: to_peer_res_list(peer_status_list),
+            }));
+        }
+
+        let accepted_peer_idx = group_list[best_group_idx].1[0];
+        let mut json = json_list[accepted_peer_idx].take().unwrap();
+        let extra_count = json.len() - 1 - tip_height as usize;
+        json.drain(..extra_count);
+        self._load_from_checked_original_format(json)?;
+
+        Ok(ConsensusRes {
+            tip_height,
+            sample_height_list,
+            peer_res_list: to_peer_res_list(peer_status_list),
+        })
+    }
+
+    // Marks peers outside of best group with first sampled height they differ at
+    fn _consensus_disagreed(
+        mut peer_status_list: Vec<Option<ConsensusPeerStatus>>,
+        group_list: &[(Vec<&str>, Vec<usize>)],
+        best_group_idx: usize,
+        sample_height_list: &[HeightType],
+    ) -> Vec<Option<ConsensusPeerStatus>> {
+        let best_key = &group_list[best_group_idx].0;
+        for (group_idx, (key, peer_idx_list)) in group_list.iter().enumerate() {
+            if group_idx == best_group_idx {
+                continue;
+            }
+            let pos = key.iter().zip(best_key).position(|(a, b)| a != b).unwrap();
+            for peer_idx in peer_idx_list {
+                peer_status_list[*peer_idx] = Some(ConsensusPeerStatus::Disagreed { height: sample_height_list[pos] });
+            }
+        }
+        peer_status_list
+    }
+}
@@ -0,0 +1,100 @@
+use serde::Serialize;
+
+use types::*;
+
+use crate::BlockIndex3IterExt;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  Diff
+//  purpose - find where two block indexes (two nodes) disagree
+//  every common height is compared, not only indep_hash: bad tx_root / weave_size can sit
+//  below fork height in broken index even if indep_hash matches
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+// chunk offsets in (weave_start, weave_end], same as OrphanedBlock
+#[derive(PartialEq, Debug, Serialize)]
+pub struct WeaveRange {
+    pub weave_start: WeaveOffsetType,
+    pub weave_end: WeaveOffsetType,
+}
+
+#[derive(PartialEq, Debug, Serialize)]
+pub struct BlockIndexDiff {
+    pub a_tip_height: Option<HeightType>,
+    pub b_tip_height: Option<HeightType>,
+    // first common height with different indep_hash, 0 if genesis differs
+    // None if one index is prefix of other
+    pub first_diverging_height: Option<HeightType>,
+    // heights where indep_hash and weave_size are same, tx_root is not
+    pub tx_root_only_list: Vec<HeightType>,
+    // heights where indep_hash and tx_root are same, weave_size is not
+    pub weave_size_only_list: Vec<HeightType>,
+    // union of block ranges of both sides for every differing common height, merged, ascending
+    // blocks above shorter tip are not counted
+    pub affected_range_list: Vec<WeaveRange>,
+    // sum of affected_range_list lengths
+    pub affected_weave_size: WeaveSizeType,
+}
+impl BlockIndexDiff {
+    pub fn is_same(&self) -> bool {
+        self.a_tip_height == self.b_tip_height
+            && self.first_diverging_height.is_none()
+            && self.tx_root_only_list.is_empty()
+            && self.weave_size_only_list.is_empty()
+    }
+}
+
+fn push_range(range_list: &mut Vec<WeaveRange>, weave_start: WeaveOffsetType, weave_end: WeaveOffsetType) {
+    if weave_start >= weave_end {
+        return;
+    }
+    if let Some(last) = range_list.last_mut() {
+        if weave_start <= last.weave_end {
+            last.weave_end = std::cmp::max(last.weave_end, weave_end);
+            return;
+        }
+    }
+    range_list.push(WeaveRange { weave_start, weave_end });
+}
+
+// Compares a and b at heights 0 ..= min(a tip, b tip)
+pub fn diff(a: &dyn BlockIndex3, b: &dyn BlockIndex3) -> BlockIndexDiff {
+    let a_tip_height = a.get_tip_height();
+    let b_tip_height = b.get_tip_height();
+    let mut ret = BlockIndexDiff {
+        a_tip_height,
+        b_tip_height,
+        first_diverging_height: None,
+        tx_root_only_list: Vec::new(),
+        weave_size_only_list: Vec::new(),
+        affected_range_list: Vec::new(),
+        affected_weave_size: 0,
+    };
+    let common_tip_height = match (a_tip_height, b_tip_height) {
+        (Some(a_tip_height), Some(b_tip_height)) => std::cmp::min(a_tip_height, b_tip_height),
+        _ => return ret,
+    };
+
+    for ((height, a_entity), (_, b_entity)) in a.iter_heights(..=common_tip_height).zip(b.iter_heights(..=common_tip_height)) {
+        let same_indep_hash = a_entity.indep_hash == b_entity.indep_hash;
+        let same_tx_root = a_entity.tx_root == b_entity.tx_root;
+        let same_weave_size = a_entity.weave_size == b_entity.weave_size;
+        if same_indep_hash && same_tx_root && same_weave_size && a_entity.block_size == b_entity.block_size {
+            continue;
+        }
+        if !same_indep_hash && ret.first_diverging_height.is_none() {
+            ret.first_diverging_height = Some(height);
+        }
+        if same_indep_hash && same_weave_size && !same_tx_root {
+            ret.tx_root_only_list.push(height);
+        }
+        if same_indep_hash && same_tx_root && !same_weave_size {
+            ret.weave_size_only_list.push(height);
+        }
+        let weave_start = std::cmp::min(a_entity.weave_size - a_entity.block_size, b_entity.weave_size - b_entity.block_size);
+        let weave_end = std::cmp::max(a_entity.weave_size, b_entity.weave_size);
+        push_range(&mut ret.affected_range_list, weave_start, weave_end);
+    }
+    ret.affected_weave_size = ret.affected_range_list.iter().map(|v| v.weave_end - v.weave_start).sum();
+    ret
+}
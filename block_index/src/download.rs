@@ -0,0 +1,193 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+// re-exported, callers do not need tokio-util dependency to cancel
+pub use tokio_util::sync::CancellationToken;
+
+use crate::{BlockIndex3Json, BlockIndexError};
+use crate::json_stream::BlockIndex3JsonStreamParser;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  Download
+//  purpose - full /block_index download over peer list with retries
+//  retryable failure (network, timeout, 5xx / 429) is retried on same peer with exponential
+//  backoff, then next peer is tried; other peer failures (4xx, garbage json, checkpoint mismatch)
+//  go to next peer right away
+//  every attempt is reported back, on success and on failure
+//  progress is reported per received chunk; cancel stops at once, index is replaced only after
+//  whole body is parsed and checked, so cancelled download leaves old index as is
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub struct DownloadConfig {
+    // whole request to one peer, including body
+    pub timeout: Duration,
+    // extra attempts on same peer for retryable failures
+    pub retry_count: usize,
+    // delay before retry n (from 0) is backoff_base * 2^n, capped by backoff_max
+    pub backoff_base: Duration,
+    pub backoff_max: Duration,
+}
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        DownloadConfig {
+            timeout: Duration::from_secs(60),
+            retry_count: 2,
+            backoff_base: Duration::from_millis(500),
+            backoff_max: Duration::from_secs(10),
+        }
+    }
+}
+impl DownloadConfig {
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let factor = 1u32.checked_shl(retry as u32).unwrap_or(u32::MAX);
+        std::cmp::min(self.backoff_base.saturating_mul(factor), self.backoff_max)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct DownloadProgress {
+    pub peer_url: String,
+    // from 0, resets on every retry and peer
+    pub attempt: usize,
+    pub byte_count: u64,
+    pub entry_count: usize,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct DownloadPeerRes {
+    pub peer_url: String,
+    // one entry per failed attempt, in order
+    pub err_list: Vec<String>,
+    pub ok: bool,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct DownloadRes {
+    pub peer_url: String,
+    // same order as tried, last one is peer_url
+    pub peer_res_list: Vec<DownloadPeerRes>,
+}
+
+// Returned as BlockIndexError::AllPeersFailed, has every attempt of every peer
+#[derive(Debug)]
+pub struct DownloadError {
+    pub peer_res_list: Vec<DownloadPeerRes>,
+}
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "all {} peers failed;", self.peer_res_list.len())?;
+        for peer_res in &self.peer_res_list {
+            write!(f, " {}: {};", peer_res.peer_url, peer_res.err_list.join(", "))?;
+        }
+        Ok(())
+    }
+}
+impl Error for DownloadError {}
+
+impl BlockIndex3Json {
+    pub async fn download(&mut self, peer_url_list: &[String]) -> Result<DownloadRes, BlockIndexError> {
+        self.download_with_config(peer_url_list, &DownloadConfig::default()).await
+    }
+
+    pub async fn download_with_config(&mut self, peer_url_list: &[String], config: &DownloadConfig) -> Result<DownloadRes, BlockIndexError> {
+        self.download_with_progress(peer_url_list, config, &mut |_| {}, &CancellationToken::new()).await
+    }
+
+    // Tries peers in order, index is replaced only by fully loaded and checked one
+    // on_progress is called on start of every attempt and after every received chunk
+    // Returns Cancelled as soon as cancel is triggered, other peers are not tried
+    pub async fn download_with_progress(
+        &mut self,
+        peer_url_list: &[String],
+        config: &DownloadConfig,
+        on_progress: &mut (dyn FnMut(&DownloadProgress) + Send),
+        cancel: &CancellationToken,
+    ) -> Result<DownloadRes, BlockIndexError> {
+        if peer_url_list.is_empty() {
+            return Err(BlockIndexError::NoPeer);
+        }
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| BlockIndexError::http("", e))?;
+
+        let mut peer_res_list = Vec::with_capacity(peer_url_list.len());
+        for peer_url in peer_url_list {
+            let mut peer_res = DownloadPeerRes {
+                peer_url: peer_url.clone(),
+                err_list: Vec::new(),
+                ok: false,
+            };
+            for attempt in 0..=config.retry_count {
+                if attempt > 0 {
+                    tokio::select! {
+                        _ = cancel.cancelled() => return Err(BlockIndexError::Cancelled),
+                        _ = tokio::time::sleep(config.backoff(attempt - 1)) => {}
+                    }
+                }
+                let mut progress = DownloadProgress {
+                    peer_url: peer_url.clone(),
+                    attempt,
+                    byte_count: 0,
+                    entry_count: 0,
+                };
+                match self._download_peer(&client, &mut progress, on_progress, cancel).await {
+                    Ok(()) => {
+                        peer_res.ok = true;
+                        break;
+                    }
+                    Err(BlockIndexError::Cancelled) => return Err(BlockIndexError::Cancelled),
+                    Err(err) => {
+                        let retryable = err.is_retryable();
+                        peer_res.err_list.push(err.to_string());
+                        if !retryable {
+                            break;
+                        }
+                    }
+                }
+            }
+            let ok = peer_res.ok;
+            peer_res_list.push(peer_res);
+            if ok {
+                return Ok(DownloadRes {
+                    peer_url: peer_url.clone(),
+                    peer_res_list,
+                });
+            }
+        }
+        Err(BlockIndexError::AllPeersFailed(DownloadError { peer_res_list }))
+    }
+
+    async fn _download_peer(
+        &mut self,
+        client: &reqwest::Client,
+        progress: &mut DownloadProgress,
+        on_progress: &mut (dyn FnMut(&DownloadProgress) + Send),
+        cancel: &CancellationToken,
+    ) -> Result<(), BlockIndexError> {
+        on_progress(progress);
+        let url = format!("{}/block_index", progress.peer_url);
+        let mut response = tokio::select! {
+            _ = cancel.cancelled() => return Err(BlockIndexError::Cancelled),
+            response = client.get(&url).send() => response.map_err(|e| BlockIndexError::http(&url, e))?,
+        };
+        if !response.status().is_success() {
+            return Err(BlockIndexError::HttpStatus { url, status: response.status().as_u16() });
+        }
+        let mut parser = BlockIndex3JsonStreamParser::new();
+        loop {
+            let chunk = tokio::select! {
+                _ = cancel.cancelled() => return Err(BlockIndexError::Cancelled),
+                chunk = response.chunk() => chunk.map_err(|e| BlockIndexError::http(&url, e))?,
+            };
+            let Some(chunk) = chunk else { break };
+            parser.feed(&chunk)?;
+            progress.byte_count += chunk.len() as u64;
+            progress.entry_count = parser.entry_count();
+            on_progress(progress);
+        }
+        let json = parser.finish()?;
+        // checked and loaded below without await, cancel after this point has no effect
+        self._load_from_checked_original_format(json)
+    }
+}
@@ -0,0 +1,120 @@
+use std::error::Error;
+use std::fmt;
+
+use types::*;
+
+use crate::{ConsensusError, DownloadError};
+
+#[derive(Debug)]
+pub enum BlockIndexError {
+    Io(std::io::Error),
+    // payload is not json or does not match /block_index entry shape
+    Json(serde_json::Error),
+    Sqlite(rusqlite::Error),
+    // idx is position in /block_index json (newest first)
+    BadBase64 { idx: usize, field: &'static str, len: usize },
+    BadWeaveSize { idx: usize },
+    NonMonotonicWeaveSize { idx: usize, prev_weave_size: WeaveSizeType, weave_size: WeaveSizeType },
+    // structure is broken (truncated stream, bad bin file, empty index, ...)
+    Corrupt(String),
+    // peer did not answer or answered with non-success status / garbage
+    Http { url: String, reason: String },
+    // peer answered with non-success status
+    HttpStatus { url: String, status: u16 },
+    // peer chain does not contain our block at height
+    NotAncestor { url: String, height: HeightType },
+    NoCommonGenesis,
+    NoPeer,
+    NoQuorum(ConsensusError),
+    AllPeersFailed(DownloadError),
+    // field is indep_hash or weave_size
+    CheckpointMismatch { height: HeightType, field: &'static str },
+    InvalidArgument(String),
+    NotLoaded,
+    Cancelled,
+}
+
+impl BlockIndexError {
+    pub(crate) fn http(url: &str, reason: impl fmt::Display) -> Self {
+        BlockIndexError::Http { url: url.to_string(), reason: reason.to_string() }
+    }
+
+    // Same peer may answer later: network failure, timeout, overloaded or broken node
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            BlockIndexError::Io(_) | BlockIndexError::Http { .. } => true,
+            BlockIndexError::HttpStatus { status, .. } => *status >= 500 || *status == 429,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for BlockIndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockIndexError::Io(err) => write!(f, "io: {}", err),
+            BlockIndexError::Json(err) => write!(f, "json: {}", err),
+            BlockIndexError::Sqlite(err) => write!(f, "sqlite: {}", err),
+            BlockIndexError::BadBase64 { idx, field, len } => write!(f, "json[{}].{} is not base64url with length {}", idx, field, len),
+            BlockIndexError::BadWeaveSize { idx } => write!(f, "json[{}].weave_size is not decimal", idx),
+            BlockIndexError::NonMonotonicWeaveSize { idx, prev_weave_size, weave_size } => {
+                write!(f, "json[{}] prev_weave_size > weave_size; {} > {}", idx, prev_weave_size, weave_size)
+            }
+            BlockIndexError::Corrupt(reason) => write!(f, "corrupt block index: {}", reason),
+            BlockIndexError::Http { url, reason } => write!(f, "{}: {}", url, reason),
+            BlockIndexError::HttpStatus { url, status } => write!(f, "{}: status {}", url, status),
+            BlockIndexError::NotAncestor { url, height } => write!(f, "{} block at height {} is not an ancestor", url, height),
+            BlockIndexError::NoCommonGenesis => write!(f, "block indexes do not share genesis"),
+            BlockIndexError::NoPeer => write!(f, "No valid peer URL found"),
+            BlockIndexError::NoQuorum(err) => write!(f, "{}", err),
+            BlockIndexError::AllPeersFailed(err) => write!(f, "{}", err),
+            BlockIndexError::CheckpointMismatch { height, field } => write!(f, "block at height {} contradicts checkpoint {}", height, field),
+            BlockIndexError::InvalidArgument(reason) => write!(f, "invalid argument: {}", reason),
+            BlockIndexError::NotLoaded => write!(f, "block index is not loaded"),
+            BlockIndexError::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl Error for BlockIndexError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BlockIndexError::Io(err) => Some(err),
+            BlockIndexError::Json(err) => Some(err),
+            BlockIndexError::Sqlite(err) => Some(err),
+            BlockIndexError::NoQuorum(err) => Some(err),
+            BlockIndexError::AllPeersFailed(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for BlockIndexError {
+    fn from(err: std::io::Error) -> Self {
+        BlockIndexError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for BlockIndexError {
+    fn from(err: serde_json::Error) -> Self {
+        BlockIndexError::Json(err)
+    }
+}
+
+impl From<rusqlite::Error> for BlockIndexError {
+    fn from(err: rusqlite::Error) -> Self {
+        BlockIndexError::Sqlite(err)
+    }
+}
+
+impl From<ConsensusError> for BlockIndexError {
+    fn from(err: ConsensusError) -> Self {
+        BlockIndexError::NoQuorum(err)
+    }
+}
+
+impl From<DownloadError> for BlockIndexError {
+    fn from(err: DownloadError) -> Self {
+        BlockIndexError::AllPeersFailed(err)
+    }
+}
@@ -0,0 +1,168 @@
+use std::ops::{Bound, Range, RangeBounds};
+
+use types::*;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  BlockIndex3Iter
+//  purpose - walk blocks of any BlockIndex3 without decoding predecessor for every entry
+//  weave_size of previous entry is carried over to compute block_size
+//  double ended, .rev() or iter_heights_rev for newest first
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub struct BlockIndex3Iter<'a, T: BlockIndex3 + ?Sized> {
+    index: &'a T,
+    // next height from front, exclusive end from back
+    front: HeightType,
+    back: HeightType,
+    // weave_size at front - 1, known after first next()
+    front_prev_weave_size: Option<WeaveSizeType>,
+    // weave_size at back - 1, known after first next_back()
+    back_weave_size: Option<WeaveSizeType>,
+}
+
+impl<'a, T: BlockIndex3 + ?Sized> BlockIndex3Iter<'a, T> {
+    fn new(index: &'a T, front: HeightType, back: HeightType) -> Self {
+        BlockIndex3Iter {
+            index,
+            front,
+            back: std::cmp::max(front, back),
+            front_prev_weave_size: None,
+            back_weave_size: None,
+        }
+    }
+
+    fn _weave_size(&self, height: HeightType) -> Option<WeaveSizeType> {
+        self.index.get_by_height_weave_size(height)
+    }
+
+    fn _prev_weave_size(&self, height: HeightType) -> Option<WeaveSizeType> {
+        if height == 0 {
+            return Some(0);
+        }
+        self._weave_size(height - 1)
+    }
+
+    fn _entity(&self, height: HeightType, weave_size: WeaveSizeType, prev_weave_size: WeaveSizeType) -> Option<(HeightType, BlockIndexEntity)> {
+        Some((height, BlockIndexEntity {
+            indep_hash: self.index.get_by_height_indep_hash(height)?,
+            weave_size,
+            tx_root: self.index.get_by_height_tx_root(height),
+            block_size: weave_size - prev_weave_size,
+        }))
+    }
+
+    // corrupt or concurrently truncated index, no entries after failed one from either end
+    fn _fuse_on_fail(&mut self, ret: Option<(HeightType, BlockIndexEntity)>) -> Option<(HeightType, BlockIndexEntity)> {
+        if ret.is_none() {
+            self.back = self.front;
+        }
+        ret
+    }
+}
+
+impl<T: BlockIndex3 + ?Sized> Iterator for BlockIndex3Iter<'_, T> {
+    type Item = (HeightType, BlockIndexEntity);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {
+            return None;
+        }
+        let height = self.front;
+        let prev_weave_size = self.front_prev_weave_size.or_else(|| self._prev_weave_size(height));
+        let ret = prev_weave_size.and_then(|prev_weave_size| {
+            let weave_size = self._weave_size(height)?;
+            self.front_prev_weave_size = Some(weave_size);
+            self._entity(height, weave_size, prev_weave_size)
+        });
+        self.front += 1;
+        self._fuse_on_fail(ret)
+    }
+
+    // lookup failure ends iteration early, so only upper bound is known
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some((self.back - self.front) as usize))
+    }
+}
+
+impl<T: BlockIndex3 + ?Sized> DoubleEndedIterator for BlockIndex3Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {
+            return None;
+        }
+        let height = self.back - 1;
+        let weave_size = self.back_weave_size.or_else(|| self._weave_size(height));
+        let ret = weave_size.and_then(|weave_size| {
+            let prev_weave_size = self._prev_weave_size(height)?;
+            self.back_weave_size = Some(prev_weave_size);
+            self._entity(height, weave_size, prev_weave_size)
+        });
+        self.back -= 1;
+        self._fuse_on_fail(ret)
+    }
+}
+
+impl<T: BlockIndex3 + ?Sized> std::iter::FusedIterator for BlockIndex3Iter<'_, T> {}
+
+pub type BlockIndex3OffsetIter<'a, T> = std::iter::Filter<BlockIndex3Iter<'a, T>, fn(&(HeightType, BlockIndexEntity)) -> bool>;
+
+pub trait BlockIndex3IterExt: BlockIndex3 {
+    // oldest first, range is clamped to [0, tip]
+    fn iter_heights<R: RangeBounds<HeightType>>(&self, range: R) -> BlockIndex3Iter<'_, Self>;
+    // newest first, same as iter_heights(range).rev()
+    fn iter_heights_rev<R: RangeBounds<HeightType>>(&self, range: R) -> std::iter::Rev<BlockIndex3Iter<'_, Self>>;
+    // blocks owning at least one chunk offset in weave_range, oldest first
+    // empty blocks (block_size 0) are skipped, see BlockIndex3::locate_offset
+    fn iter_offsets(&self, weave_range: Range<WeaveOffsetType>) -> BlockIndex3OffsetIter<'_, Self>;
+}
+
+impl<T: BlockIndex3 + ?Sized> BlockIndex3IterExt for T {
+    fn iter_heights<R: RangeBounds<HeightType>>(&self, range: R) -> BlockIndex3Iter<'_, Self> {
+        let len = match self.get_tip_height() {
+            Some(tip) => tip + 1,
+            None => return BlockIndex3Iter::new(self, 0, 0),
+        };
+        let front = match range.start_bound() {
+            Bound::Included(v) => *v,
+            Bound::Excluded(v) => v.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let back = match range.end_bound() {
+            Bound::Included(v) => v.saturating_add(1),
+            Bound::Excluded(v) => *v,
+            Bound::Unbounded => len,
+        };
+        BlockIndex3Iter::new(self, front, std::cmp::min(back, len))
+    }
+
+    fn iter_heights_rev<R: RangeBounds<HeightType>>(&self, range: R) -> std::iter::Rev<BlockIndex3Iter<'_, Self>> {
+        self.iter_heights(range).rev()
+    }
+
+    fn iter_offsets(&self, weave_range: Range<WeaveOffsetType>) -> BlockIndex3OffsetIter<'_, Self> {
+        let not_empty: fn(&(HeightType, BlockIndexEntity)) -> bool = |(_, entity)| entity.block_size > 0;
+        let empty = || BlockIndex3Iter::new(self, 0, 0).filter(not_empty);
+        if weave_range.is_empty() {
+            return empty();
+        }
+        let (genesis_weave_size, tip) = match (self.get_by_height_weave_size(0), self.get_tip_height()) {
+            (Some(genesis_weave_size), Some(tip)) => (genesis_weave_size, tip),
+            _ => return empty(),
+        };
+        let tip_weave_size = self.get_by_height_weave_size(tip).unwrap_or(genesis_weave_size);
+        // offsets below genesis weave_size are not in index
+        let first_offset = std::cmp::max(weave_range.start, genesis_weave_size);
+        let last_offset = std::cmp::min(weave_range.end - 1, tip_weave_size);
+        if first_offset > last_offset {
+            return empty();
+        }
+        let front = match self.locate_offset(first_offset) {
+            Some(location) => location.height,
+            None => return empty(),
+        };
+        let back = match self.locate_offset(last_offset) {
+            Some(location) => location.height + 1,
+            None => return empty(),
+        };
+        BlockIndex3Iter::new(self, front, back).filter(not_empty)
+    }
+}
@@ -0,0 +1,252 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use data_encoding::BASE64URL_NOPAD;
+
+use types::*;
+
+use crate::{BlockIndex3Json, BlockIndex3JsonEntity, BlockIndexError};
+use crate::json_stream::BlockIndex3JsonStreamParser;
+use crate::block_index3_bin::{encode_record, record_indep_hash, record_tx_root, record_weave_size, BLOCK_INDEX3_BIN_RECORD_SIZE};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  BlockIndex3Journal
+//  purpose - crash-safe persistence for BlockIndex3Json
+//  {path} is snapshot in original json format, written only via tmp file + rename
+//  {path}.journal is append-only log of blocks on top of snapshot
+//  journal layout: header, then records
+//    header: magic (8) | snapshot len (8, BE) | snapshot tip indep_hash (48) | crc32 (4, BE)
+//    record: height (8, BE) | bin record (97) | crc32 of both (4, BE)
+//  record means "block at height, drop everything above it", so reorg is just records from fork height + 1
+//  journal is replayed only on snapshot it was started for; crash between snapshot rename and journal
+//  reset leaves stale journal which is already in snapshot, it is dropped
+//  on open first bad record (short or wrong crc32) and everything after it is torn tail, truncated
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub const BLOCK_INDEX3_JOURNAL_MAGIC: [u8; 8] = *b"AWBI3J01";
+const JHDR_LEN_OFFSET: usize = BLOCK_INDEX3_JOURNAL_MAGIC.len();
+const JHDR_HASH_OFFSET: usize = JHDR_LEN_OFFSET + 8;
+const JHDR_CRC_OFFSET: usize = JHDR_HASH_OFFSET + INDEPHASH_LENGTH;
+pub const BLOCK_INDEX3_JOURNAL_HEADER_SIZE: usize = JHDR_CRC_OFFSET + 4;
+const JREC_HEIGHT_SIZE: usize = 8;
+const JREC_CRC_OFFSET: usize = JREC_HEIGHT_SIZE + BLOCK_INDEX3_BIN_RECORD_SIZE;
+pub const BLOCK_INDEX3_JOURNAL_RECORD_SIZE: usize = JREC_CRC_OFFSET + 4;
+// records since last snapshot which trigger compaction in append
+pub const DEFAULT_JOURNAL_COMPACT_THRESHOLD: usize = 10_000;
+
+fn path_with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut ret = path.as_os_str().to_owned();
+    ret.push(suffix);
+    PathBuf::from(ret)
+}
+
+// rename is durable only after parent dir is synced
+fn sync_parent_dir(path: &Path) -> Result<(), BlockIndexError> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+fn write_atomic(path: &Path, buf: &[u8]) -> Result<(), BlockIndexError> {
+    write_atomic_with(path, |file| Ok(file.write_all(buf)?))
+}
+
+// write fills {path}.tmp, it replaces path only once synced; readers (and maps) of old file keep old inode
+pub(crate) fn write_atomic_with<F>(path: &Path, write: F) -> Result<(), BlockIndexError>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), BlockIndexError>,
+{
+    let tmp_path = path_with_suffix(path, ".tmp");
+    let mut file = BufWriter::new(File::create(&tmp_path)?);
+    write(&mut file)?;
+    let file = file.into_inner().map_err(|err| err.into_error())?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp_path, path)?;
+    sync_parent_dir(path)
+}
+
+// len and tip of snapshot journal is started for
+fn encode_journal_header(len: usize, tip: Option<&BlockIndex3JsonEntity>) -> Result<[u8; BLOCK_INDEX3_JOURNAL_HEADER_SIZE], BlockIndexError> {
+    let mut buf = [0; BLOCK_INDEX3_JOURNAL_HEADER_SIZE];
+    buf[..JHDR_LEN_OFFSET].copy_from_slice(&BLOCK_INDEX3_JOURNAL_MAGIC);
+    buf[JHDR_LEN_OFFSET..JHDR_HASH_OFFSET].copy_from_slice(&(len as u64).to_be_bytes());
+    if let Some(tip) = tip {
+        let indep_hash = tip.decode()
+            .ok_or_else(|| BlockIndexError::Corrupt(format!("Failed to decode block at height {}", len - 1)))?
+            .indep_hash;
+        buf[JHDR_HASH_OFFSET..JHDR_CRC_OFFSET].copy_from_slice(&indep_hash);
+    }
+    let crc = crc32fast::hash(&buf[..JHDR_CRC_OFFSET]);
+    buf[JHDR_CRC_OFFSET..].copy_from_slice(&crc.to_be_bytes());
+    Ok(buf)
+}
+
+fn encode_journal_record(height: HeightType, entity: &BlockIndexEntity, buf: &mut [u8; BLOCK_INDEX3_JOURNAL_RECORD_SIZE]) {
+    buf[..JREC_HEIGHT_SIZE].copy_from_slice(&height.to_be_bytes());
+    let mut rec = [0; BLOCK_INDEX3_BIN_RECORD_SIZE];
+    encode_record(entity, &mut rec);
+    buf[JREC_HEIGHT_SIZE..JREC_CRC_OFFSET].copy_from_slice(&rec);
+    let crc = crc32fast::hash(&buf[..JREC_CRC_OFFSET]);
+    buf[JREC_CRC_OFFSET..].copy_from_slice(&crc.to_be_bytes());
+}
+
+// None if crc32 does not match
+fn decode_journal_record(buf: &[u8]) -> Option<(HeightType, BlockIndex3JsonEntity)> {
+    let crc = u32::from_be_bytes(buf[JREC_CRC_OFFSET..].try_into().unwrap());
+    if crc32fast::hash(&buf[..JREC_CRC_OFFSET]) != crc {
+        return None;
+    }
+    let height = HeightType::from_be_bytes(buf[..JREC_HEIGHT_SIZE].try_into().unwrap());
+    let rec = &buf[JREC_HEIGHT_SIZE..JREC_CRC_OFFSET];
+    Some((height, BlockIndex3JsonEntity {
+        tx_root: record_tx_root(rec).map(|v| BASE64URL_NOPAD.encode(&v)).unwrap_or_default(),
+        weave_size: record_weave_size(rec).to_string(),
+        hash: BASE64URL_NOPAD.encode(&record_indep_hash(rec)),
+    }))
+}
+
+pub struct BlockIndex3Journal {
+    snapshot_path: PathBuf,
+    journal_path: PathBuf,
+    file: File,
+    record_count: usize,
+    torn_tail_len: u64,
+    pub compact_threshold: usize,
+}
+impl BlockIndex3Journal {
+    // Loads snapshot, replays journal on top of it, truncates torn tail
+    // Missing files are created, so first open of new path gives empty index
+    pub fn open(path: &str) -> Result<(Self, BlockIndex3Json), BlockIndexError> {
+        let snapshot_path = PathBuf::from(path);
+        let journal_path = path_with_suffix(&snapshot_path, ".journal");
+
+        // oldest first while replaying
+        let mut block_list = if snapshot_path.exists() {
+            // empty snapshot is valid here
+            let mut block_list = BlockIndex3JsonStreamParser::parse_reader(File::open(&snapshot_path)?)?;
+            block_list.reverse();
+            block_list
+        } else {
+            Vec::new()
+        };
+
+        let header = encode_journal_header(block_list.len(), block_list.last())?;
+        if !journal_path.exists() {
+            // new path or snapshot without journal
+            write_atomic(&journal_path, &header)?;
+        }
+        let mut buf = Vec::new();
+        File::open(&journal_path)?.read_to_end(&mut buf)?;
+        let magic_len = std::cmp::min(buf.len(), BLOCK_INDEX3_JOURNAL_MAGIC.len());
+        if buf[..magic_len] != BLOCK_INDEX3_JOURNAL_MAGIC[..magic_len] || buf.len() < BLOCK_INDEX3_JOURNAL_HEADER_SIZE {
+            // header is written only via rename, so it is never torn
+            return Err(BlockIndexError::Corrupt(format!("bad header, {} is not a BlockIndex3Journal file", journal_path.display())));
+        }
+        let crc = u32::from_be_bytes(buf[JHDR_CRC_OFFSET..BLOCK_INDEX3_JOURNAL_HEADER_SIZE].try_into().unwrap());
+        if crc32fast::hash(&buf[..JHDR_CRC_OFFSET]) != crc {
+            return Err(BlockIndexError::Corrupt(format!("bad header crc32 in {}", journal_path.display())));
+        }
+        if buf[..BLOCK_INDEX3_JOURNAL_HEADER_SIZE] != header {
+            // stale journal of previous snapshot, all its records are in snapshot already
+            write_atomic(&journal_path, &header)?;
+            buf = header.to_vec();
+        }
+
+        let mut good_len = BLOCK_INDEX3_JOURNAL_HEADER_SIZE;
+        let mut record_count = 0;
+        for rec in buf[BLOCK_INDEX3_JOURNAL_HEADER_SIZE..].chunks_exact(BLOCK_INDEX3_JOURNAL_RECORD_SIZE) {
+            let (height, entity) = match decode_journal_record(rec) {
+                Some(v) => v,
+                None => break,
+            };
+            Self::_replay(&mut block_list, height, entity)?;
+            good_len += BLOCK_INDEX3_JOURNAL_RECORD_SIZE;
+            record_count += 1;
+        }
+
+        let torn_tail_len = (buf.len() - good_len) as u64;
+        let file = OpenOptions::new().append(true).open(&journal_path)?;
+        if torn_tail_len > 0 {
+            file.set_len(good_len as u64)?;
+            file.sync_all()?;
+        }
+
+        block_list.reverse();
+        let mut index = BlockIndex3Json::new();
+        if !block_list.is_empty() {
+            index._load_from_original_format(block_list)?;
+        }
+
+        Ok((BlockIndex3Journal {
+            snapshot_path,
+            journal_path,
+            file,
+            record_count,
+            torn_tail_len,
+            compact_threshold: DEFAULT_JOURNAL_COMPACT_THRESHOLD,
+        }, index))
+    }
+
+    // block_list is oldest first
+    fn _replay(block_list: &mut Vec<BlockIndex3JsonEntity>, height: HeightType, entity: BlockIndex3JsonEntity) -> Result<(), BlockIndexError> {
+        let idx = height as usize;
+        if idx > block_list.len() {
+            return Err(BlockIndexError::Corrupt(format!("journal record at height {} is above tip {}", height, block_list.len() as i128 - 1)));
+        }
+        block_list.truncate(idx);
+        block_list.push(entity);
+        Ok(())
+    }
+
+    // Bytes dropped from journal tail by last open
+    pub fn torn_tail_len(&self) -> u64 {
+        self.torn_tail_len
+    }
+
+    // Records in journal since last snapshot
+    pub fn record_count(&self) -> usize {
+        self.record_count
+    }
+
+    // Appends blocks of index at from_height..=tip and fsyncs
+    // After sync() pass old tip + 1, after reorg() pass fork_height + 1
+    // Compacts when record_count reaches compact_threshold
+    pub fn append(&mut self, index: &BlockIndex3Json, from_height: HeightType) -> Result<(), BlockIndexError> {
+        let len = index.block_list.len();
+        if from_height as usize >= len {
+            return Ok(());
+        }
+        let mut buf = Vec::with_capacity((len - from_height as usize) * BLOCK_INDEX3_JOURNAL_RECORD_SIZE);
+        let mut rec = [0; BLOCK_INDEX3_JOURNAL_RECORD_SIZE];
+        for height in from_height..len as HeightType {
+            let el = &index.block_list[len - 1 - height as usize];
+            let entity = el.decode().ok_or_else(|| BlockIndexError::Corrupt(format!("Failed to decode block at height {}", height)))?;
+            encode_journal_record(height, &entity, &mut rec);
+            buf.extend_from_slice(&rec);
+        }
+        self.file.write_all(&buf)?;
+        self.file.sync_data()?;
+        self.record_count += len - from_height as usize;
+
+        if self.record_count >= self.compact_threshold {
+            self.compact(index)?;
+        }
+        Ok(())
+    }
+
+    // Writes whole index as new snapshot and resets journal, both via rename
+    // Crash in between leaves old journal next to new snapshot, open drops it by header
+    pub fn compact(&mut self, index: &BlockIndex3Json) -> Result<(), BlockIndexError> {
+        let header = encode_journal_header(index.block_list.len(), index.block_list.first())?;
+        let block_list = serde_json::to_string(&index.block_list)?;
+        write_atomic(&self.snapshot_path, block_list.as_bytes())?;
+        write_atomic(&self.journal_path, &header)?;
+        self.file = OpenOptions::new().append(true).open(&self.journal_path)?;
+        self.record_count = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use std::io::Read;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use types::*;
+
+use crate::{orig_format3_json_entity_check, BlockIndex3JsonEntity, BlockIndexError};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  BlockIndex3JsonStreamParser
+//  purpose - parse /block_index json array entry by entry as bytes arrive
+//  never holds whole payload, only current entry
+//  runs orig_format3_json_check rules per entry
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub(crate) const STREAM_READ_BUF_SIZE: usize = 64 * 1024;
+// entry is ~170 bytes, anything much bigger is garbage
+const STREAM_ENTRY_MAX_SIZE: usize = 4 * 1024;
+
+#[derive(PartialEq, Debug)]
+enum StreamState {
+    ArrayStart,
+    EntryOrArrayEnd,
+    Entry,
+    CommaOrArrayEnd,
+    Done,
+}
+
+pub(crate) struct BlockIndex3JsonStreamParser {
+    state: StreamState,
+    entry_buf: Vec<u8>,
+    in_string: bool,
+    escape: bool,
+    prev_weave_size: Option<WeaveSizeType>,
+    block_list: Vec<BlockIndex3JsonEntity>,
+}
+impl BlockIndex3JsonStreamParser {
+    pub fn new() -> Self {
+        BlockIndex3JsonStreamParser {
+            state: StreamState::ArrayStart,
+            entry_buf: Vec::new(),
+            in_string: false,
+            escape: false,
+            prev_weave_size: None,
+            block_list: Vec::new(),
+        }
+    }
+
+    fn _push_entry(&mut self) -> Result<(), BlockIndexError> {
+        let i = self.block_list.len();
+        let entity: BlockIndex3JsonEntity = serde_json::from_slice(&self.entry_buf)
+            .map_err(|e| BlockIndexError::Corrupt(format!("json[{}] {}", i, e)))?;
+        self.entry_buf.clear();
+
+        let weave_size = orig_format3_json_entity_check(i, &entity)?;
+        // newest first, weave_size must not grow
+        if let Some(prev_weave_size) = self.prev_weave_size {
+            if weave_size > prev_weave_size {
+                return Err(BlockIndexError::NonMonotonicWeaveSize { idx: i - 1, prev_weave_size: weave_size, weave_size: prev_weave_size });
+            }
+        }
+        self.prev_weave_size = Some(weave_size);
+        self.block_list.push(entity);
+        Ok(())
+    }
+
+    pub fn feed(&mut self, buf: &[u8]) -> Result<(), BlockIndexError> {
+        for &ch in buf {
+            match self.state {
+                StreamState::Entry => {
+                    self.entry_buf.push(ch);
+                    if self.entry_buf.len() > STREAM_ENTRY_MAX_SIZE {
+                        return Err(BlockIndexError::Corrupt(format!("json[{}] entry is too big", self.block_list.len())));
+                    }
+                    if self.in_string {
+                        if self.escape {
+                            self.escape = false;
+                        } else if ch == b'\\' {
+                            self.escape = true;
+                        } else if ch == b'"' {
+                            self.in_string = false;
+                        }
+                        continue;
+                    }
+                    match ch {
+                        b'"' => self.in_string = true,
+                        b'{' | b'[' => return Err(BlockIndexError::Corrupt(format!("json[{}] nested value is not expected", self.block_list.len()))),
+                        b'}' => {
+                            self._push_entry()?;
+                            self.state = StreamState::CommaOrArrayEnd;
+                        }
+                        _ => {}
+                    }
+                }
+                _ if ch.is_ascii_whitespace() => {}
+                StreamState::ArrayStart if ch == b'[' => self.state = StreamState::EntryOrArrayEnd,
+                StreamState::EntryOrArrayEnd if ch == b']' && self.block_list.is_empty() => self.state = StreamState::Done,
+                StreamState::EntryOrArrayEnd if ch == b'{' => {
+                    self.entry_buf.push(ch);
+                    self.state = StreamState::Entry;
+                }
+                StreamState::CommaOrArrayEnd if ch == b',' => self.state = StreamState::EntryOrArrayEnd,
+                StreamState::CommaOrArrayEnd if ch == b']' => self.state = StreamState::Done,
+                _ => return Err(BlockIndexError::Corrupt(format!("unexpected byte '{}' after json[{}] in state {:?}", ch as char, self.block_list.len(), self.state))),
+            }
+        }
+        Ok(())
+    }
+
+    // entries parsed so far
+    pub fn entry_count(&self) -> usize {
+        self.block_list.len()
+    }
+
+    // newest first, same as /block_index
+    pub fn finish(self) -> Result<Vec<BlockIndex3JsonEntity>, BlockIndexError> {
+        if self.state != StreamState::Done {
+            return Err(BlockIndexError::Corrupt(format!("unexpected end of json after json[{}]", self.block_list.len())));
+        }
+        Ok(self.block_list)
+    }
+
+    pub fn parse_reader<R: Read>(mut reader: R) -> Result<Vec<BlockIndex3JsonEntity>, BlockIndexError> {
+        let mut parser = Self::new();
+        let mut buf = vec![0; STREAM_READ_BUF_SIZE];
+        loop {
+            let len = reader.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            parser.feed(&buf[..len])?;
+        }
+        parser.finish()
+    }
+
+    pub async fn parse_async_reader<R: AsyncRead + Unpin>(mut reader: R) -> Result<Vec<BlockIndex3JsonEntity>, BlockIndexError> {
+        let mut parser = Self::new();
+        let mut buf = vec![0; STREAM_READ_BUF_SIZE];
+        loop {
+            let len = reader.read(&mut buf).await?;
+            if len == 0 {
+                break;
+            }
+            parser.feed(&buf[..len])?;
+        }
+        parser.finish()
+    }
+
+    pub async fn parse_response(mut response: reqwest::Response) -> Result<Vec<BlockIndex3JsonEntity>, BlockIndexError> {
+        let url = response.url().to_string();
+        let mut parser = Self::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| BlockIndexError::http(&url, e))? {
+            parser.feed(&chunk)?;
+        }
+        parser.finish()
+    }
+}
@@ -1,14 +1,46 @@
-use std::error::Error;
 use std::fs::File;
 use std::io::{Read, Write};
+use tokio::io::AsyncRead;
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use serde_json;
 use regex::Regex;
 use data_encoding::BASE64URL_NOPAD;
-use reqwest;
 
 use types::*;
 
+mod block_index3_bin;
+pub use block_index3_bin::*;
+mod block_index3_decoded;
+pub use block_index3_decoded::*;
+mod block_index3_sqlite;
+pub use block_index3_sqlite::*;
+mod reorg;
+pub use reorg::*;
+mod consensus;
+pub use consensus::*;
+mod download;
+pub use download::*;
+mod json_stream;
+use json_stream::BlockIndex3JsonStreamParser;
+mod error;
+pub use error::*;
+mod reverse_map;
+use reverse_map::ReverseMap;
+mod iter;
+pub use iter::*;
+mod journal;
+pub use journal::*;
+mod checkpoint;
+pub use checkpoint::*;
+mod diff;
+pub use diff::*;
+mod server;
+pub use server::*;
+mod peer_manager;
+pub use peer_manager::*;
+mod shared;
+pub use shared::*;
+
 // TODO move to separate file
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 //  BlockIndex3Json
@@ -18,7 +50,7 @@ use types::*;
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 struct BlockIndex3JsonEntity {
     tx_root: String,
     weave_size: String,
@@ -51,36 +83,76 @@ impl BlockIndex3JsonEntity {
 }
 
 
-fn orig_format3_json_check(json: &[BlockIndex3JsonEntity]) -> Result<(), String> {
-    let tx_root_regex = Regex::new(r"(?i)^[-_a-z0-9]{43}$").unwrap();
-    let weave_size_regex = Regex::new(r"^\d+$").unwrap();
-    let hash_regex = Regex::new(r"(?i)^[-_a-z0-9]{64}$").unwrap();
+static TX_ROOT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)^[-_a-z0-9]{43}$").unwrap());
+static WEAVE_SIZE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\d+$").unwrap());
+static HASH_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)^[-_a-z0-9]{64}$").unwrap());
 
-    if !json.is_empty() {
-        for (i, el) in json.iter().enumerate() {
-            if el.tx_root != "" && !tx_root_regex.is_match(&el.tx_root) {
-                return Err(format!("json[{}].tx_root is not base64url with length 43", i));
-            }
-            if !weave_size_regex.is_match(&el.weave_size) {
-                return Err(format!("json[{}].weave_size is not decimal", i));
-            }
-            if !hash_regex.is_match(&el.hash) {
-                println!("{}", el.hash);
-                return Err(format!("json[{}].hash is not base64url with length 64", i));
-            }
-        }
-        let mut prev_weave_size: WeaveSizeType = json[json.len() - 1].weave_size.parse().map_err(|_| format!("Failed to parse weave_size at index {}", json.len() - 1))?;
-        for i in (0..json.len() - 1).rev() {
-            let weave_size: WeaveSizeType = json[i].weave_size.parse().map_err(|_| format!("Failed to parse weave_size at index {}", i))?;
-            if prev_weave_size > weave_size {
-                return Err(format!("json[{}] prev_weave_size > weave_size; {} > {}", i, prev_weave_size, weave_size));
-            }
-            prev_weave_size = weave_size;
+// Checks single entry, returns parsed weave_size
+fn orig_format3_json_entity_check(i: usize, el: &BlockIndex3JsonEntity) -> Result<WeaveSizeType, BlockIndexError> {
+    if !el.tx_root.is_empty() && !TX_ROOT_REGEX.is_match(&el.tx_root) {
+        return Err(BlockIndexError::BadBase64 { idx: i, field: "tx_root", len: 43 });
+    }
+    if !WEAVE_SIZE_REGEX.is_match(&el.weave_size) {
+        return Err(BlockIndexError::BadWeaveSize { idx: i });
+    }
+    if !HASH_REGEX.is_match(&el.hash) {
+        return Err(BlockIndexError::BadBase64 { idx: i, field: "hash", len: 64 });
+    }
+    // regex passed, only overflow is left
+    el.weave_size.parse().map_err(|_| BlockIndexError::BadWeaveSize { idx: i })
+}
+
+fn orig_format3_json_check(json: &[BlockIndex3JsonEntity]) -> Result<(), BlockIndexError> {
+    let mut weave_size_list = Vec::with_capacity(json.len());
+    for (i, el) in json.iter().enumerate() {
+        weave_size_list.push(orig_format3_json_entity_check(i, el)?);
+    }
+    for i in (0..json.len().saturating_sub(1)).rev() {
+        let prev_weave_size = weave_size_list[i + 1];
+        let weave_size = weave_size_list[i];
+        if prev_weave_size > weave_size {
+            return Err(BlockIndexError::NonMonotonicWeaveSize { idx: i, prev_weave_size, weave_size });
         }
     }
     Ok(())
+}
 
-    // ... rest of the code ...
+fn block_location(height: HeightType, entity: BlockIndexEntity) -> BlockLocation {
+    BlockLocation {
+        height,
+        block_start: entity.weave_size - entity.block_size,
+        block_end: entity.weave_size,
+        block_size: entity.block_size,
+        tx_root: entity.tx_root,
+    }
+}
+
+// Max heights requested in one /block_index/{from}/{to} call
+pub const BLOCK_INDEX_RANGE_MAX: HeightType = 1000;
+
+#[derive(Deserialize)]
+struct PeerInfoJson {
+    height: HeightType,
+}
+
+async fn fetch_peer_height(client: &reqwest::Client, peer_url: &str) -> Result<HeightType, BlockIndexError> {
+    let url = format!("{}/info", peer_url);
+    let bytes = client.get(&url).send().await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| BlockIndexError::http(&url, e))?
+        .bytes().await
+        .map_err(|e| BlockIndexError::http(&url, e))?;
+    let info: PeerInfoJson = serde_json::from_slice(&bytes).map_err(|e| BlockIndexError::http(&url, e))?;
+    Ok(info.height)
+}
+
+// Ok(None) means peer does not serve this url (e.g. ranged endpoint is not supported)
+async fn fetch_block_index_json(client: &reqwest::Client, url: &str) -> Result<Option<Vec<BlockIndex3JsonEntity>>, BlockIndexError> {
+    let response = client.get(url).send().await.map_err(|e| BlockIndexError::http(url, e))?;
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+    Ok(Some(BlockIndex3JsonStreamParser::parse_response(response).await?))
 }
 
 #[derive(PartialEq, Debug)]
@@ -89,10 +161,19 @@ struct BlockJsonIdxRet<'a> {
     block_index_json_entity: &'a BlockIndex3JsonEntity,
 }
 
+#[derive(Clone)]
 pub struct BlockIndex3Json {
     block_list: Vec<BlockIndex3JsonEntity>,
     chunk_offset_a: WeaveOffsetType,
     chunk_offset_b: WeaveOffsetType,
+    reverse_map: ReverseMap,
+    // loaded / appended blocks must match, see set_checkpoint_list
+    checkpoint_list: Option<CheckpointList>,
+}
+impl Default for BlockIndex3Json {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl BlockIndex3Json {
     pub fn new() -> Self {
@@ -100,52 +181,95 @@ impl BlockIndex3Json {
             block_list: Vec::new(),
             chunk_offset_a: 0,
             chunk_offset_b: 0,
+            reverse_map: ReverseMap::new(),
+            checkpoint_list: None,
         }
     }
 
-    fn _load_from_original_format(&mut self, json: Vec<BlockIndex3JsonEntity>) -> Result<(), Box<dyn Error>> {
+    // With checkpoints set, load*, download*, sync and reorg reject blocks contradicting them
+    // and leave current index untouched; download and sync try next peer
+    // Does not check already loaded blocks, use verify_against_checkpoints for that
+    pub fn set_checkpoint_list(&mut self, checkpoint_list: Option<CheckpointList>) {
+        self.checkpoint_list = checkpoint_list;
+    }
+
+    pub fn checkpoint_list(&self) -> Option<&CheckpointList> {
+        self.checkpoint_list.as_ref()
+    }
+
+    // block_list[..count] (newest first) are new blocks on top of the rest
+    fn _reverse_map_insert_top(&mut self, count: usize) {
+        let base_height = self.block_list.len() - count;
+        for (i, el) in self.block_list[..count].iter().rev().enumerate() {
+            // checked json always decodes
+            if let Some(entity) = el.decode() {
+                self.reverse_map.insert((base_height + i) as HeightType, entity.indep_hash, entity.tx_root);
+            }
+        }
+    }
+
+    fn _load_from_original_format(&mut self, json: Vec<BlockIndex3JsonEntity>) -> Result<(), BlockIndexError> {
         orig_format3_json_check(&json)?;
+        self._load_from_checked_original_format(json)
+    }
+
+    // json must pass orig_format3_json_check (BlockIndex3JsonStreamParser does it on the fly)
+    fn _load_from_checked_original_format(&mut self, json: Vec<BlockIndex3JsonEntity>) -> Result<(), BlockIndexError> {
+        if json.is_empty() {
+            return Err(BlockIndexError::Corrupt("empty block index".into()));
+        }
+        if let Some(checkpoint_list) = &self.checkpoint_list {
+            checkpoint_list.verify_block_list(&json, 0)?;
+        }
+        self.chunk_offset_a = json[json.len() - 1].weave_size.parse().map_err(|_| BlockIndexError::BadWeaveSize { idx: json.len() - 1 })?;
+        self.chunk_offset_b = json[0].weave_size.parse().map_err(|_| BlockIndexError::BadWeaveSize { idx: 0 })?;
+        self.reverse_map = ReverseMap::with_capacity(json.len());
         self.block_list = json;
-        self.chunk_offset_a = self.block_list[self.block_list.len() - 1].weave_size.parse().unwrap();
-        self.chunk_offset_b = self.block_list[0].weave_size.parse().unwrap();
+        self._reverse_map_insert_top(self.block_list.len());
         Ok(())
     }
 
+    // Parses entries directly off reader, whole payload is never held in memory
+    pub fn load_from_reader<R: Read>(&mut self, reader: R) -> Result<(), BlockIndexError> {
+        let json = BlockIndex3JsonStreamParser::parse_reader(reader)?;
+        self._load_from_checked_original_format(json)
+    }
+
+    pub async fn load_from_async_reader<R: AsyncRead + Unpin>(&mut self, reader: R) -> Result<(), BlockIndexError> {
+        let json = BlockIndex3JsonStreamParser::parse_async_reader(reader).await?;
+        self._load_from_checked_original_format(json)
+    }
+
     // WARNING impl is actually not async
-    pub async fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+    pub async fn save(&self, path: &str) -> Result<(), BlockIndexError> {
         let mut file = File::create(path)?;
         let block_list = serde_json::to_string(&self.block_list)?;
         file.write_all(block_list.as_bytes())?;
         Ok(())
     }
 
-    // WARNING impl is actually not async
-    pub async fn load(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
-        let mut file = File::open(path)?;
-        let mut cont = String::new();
-        file.read_to_string(&mut cont)?;
-        let json: Vec<BlockIndex3JsonEntity> = serde_json::from_str(&cont)?;
-        self._load_from_original_format(json)?;
-        Ok(())
+    pub async fn load(&mut self, path: &str) -> Result<(), BlockIndexError> {
+        let file = tokio::fs::File::open(path).await?;
+        self.load_from_async_reader(file).await
     }
 
-    pub fn save_sync(&self, path: &str) -> Result<(), Box<dyn Error>> {
+    pub fn save_sync(&self, path: &str) -> Result<(), BlockIndexError> {
         let mut file = File::create(path)?;
         let block_list = serde_json::to_string(&self.block_list)?;
         file.write_all(block_list.as_bytes())?;
         Ok(())
     }
 
-    pub fn load_sync(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
-        let mut file = File::open(path)?;
-        let mut cont = String::new();
-        file.read_to_string(&mut cont)?;
-        let json: Vec<BlockIndex3JsonEntity> = serde_json::from_str(&cont)?;
-        self._load_from_original_format(json)?;
-        Ok(())
+    pub fn load_sync(&mut self, path: &str) -> Result<(), BlockIndexError> {
+        let file = File::open(path)?;
+        self.load_from_reader(file)
     }
 
-    fn _get_block_idx_by_chunk_offset(&self, chunk_offset: WeaveOffsetType) -> Option<BlockJsonIdxRet> {
+    fn _get_block_idx_by_chunk_offset(&self, chunk_offset: WeaveOffsetType) -> Option<BlockJsonIdxRet<'_>> {
+        // empty index has zero range, offset 0 passes check below
+        if self.block_list.is_empty() {
+            return None;
+        }
         if self.chunk_offset_a > chunk_offset || self.chunk_offset_b < chunk_offset {
             return None;
         }
@@ -158,12 +282,14 @@ impl BlockIndex3Json {
 
         let mut ret_block_idx;
         loop {
+            // block owns its end offset, equal run is resolved to oldest below
             if co_c == chunk_offset {
-                ret_block_idx = idx_c - 1;
+                ret_block_idx = idx_c;
                 break;
             }
             if idx_c == idx_b {
-                ret_block_idx = idx_b;
+                // idx_a end is below chunk_offset, except genesis end hit exactly
+                ret_block_idx = if self.block_list[idx_a].weave_size.parse::<WeaveSizeType>().unwrap() == chunk_offset { idx_a } else { idx_b };
                 break;
             }
 
@@ -194,35 +320,183 @@ impl BlockIndex3Json {
       })
     }
 
-    pub async fn download(&mut self, peer_url_list: &[String]) -> Result<(), Box<dyn Error>> {
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(60))
-            .build()?;
+    // Fetches blocks above (tip_height, peer_height] from ranged endpoint
+    // Returns newest first, same as block_list
+    // Ok(None) if peer has no ranged endpoint
+    async fn _sync_fetch_ranged(&self, client: &reqwest::Client, peer_url: &str, peer_height: HeightType) -> Result<Option<Vec<BlockIndex3JsonEntity>>, BlockIndexError> {
+        let mut anchor_height = (self.block_list.len() - 1) as HeightType;
+        let mut anchor_hash = self.block_list[0].hash.clone();
+        // oldest first
+        let mut new_block_list: Vec<BlockIndex3JsonEntity> = Vec::new();
+        while anchor_height < peer_height {
+            let to = std::cmp::min(anchor_height + BLOCK_INDEX_RANGE_MAX, peer_height);
+            let url = format!("{}/block_index/{}/{}", peer_url, anchor_height, to);
+            let mut range = match fetch_block_index_json(client, &url).await? {
+                Some(range) => range,
+                None => return Ok(None),
+            };
+            if range.len() as HeightType != to - anchor_height + 1 {
+                return Err(BlockIndexError::http(&url, format!("returned {} blocks, expected {}", range.len(), to - anchor_height + 1)));
+            }
+            // peers are not consistent about range order, anchor tells
+            if range[range.len() - 1].hash == anchor_hash {
+                range.reverse();
+            }
+            if range[0].hash != anchor_hash {
+                return Err(BlockIndexError::NotAncestor { url, height: anchor_height });
+            }
+            anchor_hash = range[range.len() - 1].hash.clone();
+            anchor_height = to;
+            new_block_list.extend(range.into_iter().skip(1));
+        }
+        new_block_list.reverse();
+        Ok(Some(new_block_list))
+    }
 
-        let mut last_err: Box<dyn Error> = "No valid peer URL found".into();
+    // Fallback for peers without ranged endpoint
+    async fn _sync_fetch_full(&self, client: &reqwest::Client, peer_url: &str) -> Result<Vec<BlockIndex3JsonEntity>, BlockIndexError> {
+        let url = format!("{}/block_index", peer_url);
+        let mut json = fetch_block_index_json(client, &url).await?.ok_or_else(|| BlockIndexError::http(&url, "is not available"))?;
+        let tip_idx = json.len().checked_sub(self.block_list.len())
+            .ok_or_else(|| BlockIndexError::http(&url, "is shorter than local block index"))?;
+        if json[tip_idx].hash != self.block_list[0].hash {
+            return Err(BlockIndexError::NotAncestor { url, height: (self.block_list.len() - 1) as HeightType });
+        }
+        json.truncate(tip_idx);
+        Ok(json)
+    }
+
+    fn _append_new_blocks(&mut self, mut new_block_list: Vec<BlockIndex3JsonEntity>) -> Result<(), BlockIndexError> {
+        if new_block_list.is_empty() {
+            return Ok(());
+        }
+        orig_format3_json_check(&new_block_list)?;
+        let tip_weave_size: WeaveSizeType = self.block_list[0].weave_size.parse().map_err(|_| BlockIndexError::BadWeaveSize { idx: 0 })?;
+        let new_idx = new_block_list.len() - 1;
+        let new_weave_size: WeaveSizeType = new_block_list[new_idx].weave_size.parse().map_err(|_| BlockIndexError::BadWeaveSize { idx: new_idx })?;
+        if tip_weave_size > new_weave_size {
+            return Err(BlockIndexError::NonMonotonicWeaveSize { idx: new_idx, prev_weave_size: tip_weave_size, weave_size: new_weave_size });
+        }
+        if let Some(checkpoint_list) = &self.checkpoint_list {
+            checkpoint_list.verify_block_list(&new_block_list, self.block_list.len() as HeightType)?;
+        }
+        let count = new_block_list.len();
+        new_block_list.append(&mut self.block_list);
+        self.block_list = new_block_list;
+        self.chunk_offset_b = self.block_list[0].weave_size.parse().map_err(|_| BlockIndexError::BadWeaveSize { idx: 0 })?;
+        self._reverse_map_insert_top(count);
+        Ok(())
+    }
+
+    // Appends blocks above current tip, old tip must be an ancestor of peer's tip
+    // Uses /block_index/{from}/{to} if peer supports it, full /block_index otherwise
+    // Returns count of appended blocks, 0 if no peer is ahead
+    // Err if every peer ahead failed, even if other peers answered with lower tip
+    pub async fn sync(&mut self, peer_url_list: &[String]) -> Result<usize, BlockIndexError> {
+        self.sync_with_config(peer_url_list, &DownloadConfig::default()).await
+    }
+
+    // config.timeout applies to every request, retries are used only for initial download
+    pub async fn sync_with_config(&mut self, peer_url_list: &[String], config: &DownloadConfig) -> Result<usize, BlockIndexError> {
+        if self.block_list.is_empty() {
+            self.download_with_config(peer_url_list, config).await?;
+            return Ok(self.block_list.len());
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| BlockIndexError::http("", e))?;
+
+        let tip_height = (self.block_list.len() - 1) as HeightType;
+        let mut up_to_date = false;
+        let mut last_err = BlockIndexError::NoPeer;
+        // error of last peer which is ahead of local tip
+        let mut ahead_err = None;
         for peer_url in peer_url_list {
-            let url = format!("{}/block_index", peer_url);
-            println!("{}", url);
-            match client.get(&url).send().await {
-                Ok(response) => {
-                    let bytes = response.bytes().await?;
-                    let json_str = String::from_utf8(bytes.to_vec())?;
-                    let json: Vec<BlockIndex3JsonEntity> = serde_json::from_str(&json_str)?;
-                    self._load_from_original_format(json)?;
-                    return Ok(());
-                }
+            let peer_height = match fetch_peer_height(&client, peer_url).await {
+                Ok(peer_height) => peer_height,
                 Err(err) => {
-                    last_err = err.into();
+                    last_err = err;
+                    continue;
                 }
+            };
+            if peer_height <= tip_height {
+                up_to_date = true;
+                continue;
+            }
+            let new_block_list = match self._sync_fetch_ranged(&client, peer_url, peer_height).await {
+                Ok(Some(new_block_list)) => Ok(new_block_list),
+                Ok(None) => self._sync_fetch_full(&client, peer_url).await,
+                Err(err) => Err(err),
+            };
+            match new_block_list.and_then(|new_block_list| {
+                let count = new_block_list.len();
+                self._append_new_blocks(new_block_list).map(|_| count)
+            }) {
+                Ok(count) => return Ok(count),
+                Err(err) => ahead_err = Some(err),
             }
         }
 
+        // lagging peer does not make us up to date if peer ahead of us failed
+        if let Some(err) = ahead_err {
+            return Err(err);
+        }
+        if up_to_date {
+            return Ok(0);
+        }
         Err(last_err)
     }
+
+    // Rolls back local tail to fork height with other and applies other's branch on top
+    // No-op if other has nothing above fork height (other is behind or same)
+    // NOTE. does not decide which branch is better, caller does
+    pub fn reorg(&mut self, other: &dyn BlockIndex3) -> Result<ReorgRes, BlockIndexError> {
+        let fork_height = find_fork_height(self, other).ok_or(BlockIndexError::NoCommonGenesis)?;
+        let fork_weave_size = self.get_by_height_weave_size(fork_height)
+            .ok_or_else(|| BlockIndexError::Corrupt(format!("Failed to parse weave_size at height {}", fork_height)))?;
+        // find_fork_height found common block, other is not empty
+        let other_tip = other.get_tip_height().unwrap();
+        if other_tip == fork_height {
+            return Ok(reorg_res(fork_height, Vec::new(), fork_weave_size, 0));
+        }
+
+        // newest first, same as block_list
+        let mut new_block_list = Vec::with_capacity((other_tip - fork_height) as usize);
+        for height in (fork_height + 1..=other_tip).rev() {
+            let err = || BlockIndexError::Corrupt(format!("other block index has no block at height {}", height));
+            new_block_list.push(BlockIndex3JsonEntity {
+                tx_root: other.get_by_height_tx_root_orig(height).ok_or_else(err)?,
+                weave_size: other.get_by_height_weave_size_orig(height).ok_or_else(err)?,
+                hash: other.get_by_height_indep_hash_orig(height).ok_or_else(err)?,
+            });
+        }
+        let applied_count = new_block_list.len();
+        let orphaned_list = orphaned_list(self, fork_height);
+
+        let rollback_count = self.block_list.len() - (fork_height as usize + 1);
+        let mut orphaned_block_list: Vec<BlockIndex3JsonEntity> = self.block_list.drain(..rollback_count).collect();
+        for orphaned in &orphaned_list {
+            self.reverse_map.remove(orphaned.height, &orphaned.indep_hash, orphaned.tx_root.as_ref());
+        }
+        if let Err(err) = self._append_new_blocks(new_block_list) {
+            orphaned_block_list.append(&mut self.block_list);
+            self.block_list = orphaned_block_list;
+            self._reverse_map_insert_top(rollback_count);
+            return Err(err);
+        }
+
+        Ok(reorg_res(fork_height, orphaned_list, fork_weave_size, applied_count))
+    }
 }
 
 impl BlockIndex for BlockIndex3Json {}
 impl BlockIndex3 for BlockIndex3Json {
+    fn get_tip_height(&self) -> Option<HeightType> {
+        (self.block_list.len() as HeightType).checked_sub(1)
+    }
+
     fn get_by_height_full(&self, height: HeightType) -> Option<BlockIndexEntity> {
         let idx = self.block_list.len().checked_sub(height as usize + 1)?;
 
@@ -264,7 +538,7 @@ impl BlockIndex3 for BlockIndex3Json {
     fn get_by_height_weave_size(&self, height: HeightType) -> Option<WeaveSizeType> {
         let idx = self.block_list.len().checked_sub(height as usize + 1)?;
         let block_index_json_entity = self.block_list.get(idx)?;
-        Some(block_index_json_entity.weave_size.parse().ok()?)
+        block_index_json_entity.weave_size.parse().ok()
     }
 
     fn get_by_height_tx_root(&self, height: HeightType) -> Option<TxRootType> {
@@ -304,14 +578,20 @@ impl BlockIndex3 for BlockIndex3Json {
             .unwrap_or(0);
 
         let mut indep_hash: IndepHashType = [0; INDEPHASH_LENGTH];
-        let mut tx_root: TxRootType = [0; TXROOT_LENGTH];
         BASE64URL_NOPAD.decode_mut(block_index_json_entity.hash.as_bytes(), &mut indep_hash).ok()?;
-        BASE64URL_NOPAD.decode_mut(block_index_json_entity.tx_root.as_bytes(), &mut tx_root).ok()?;
+
+        let tx_root = if block_index_json_entity.tx_root.is_empty() {
+            None
+        } else {
+            let mut tx_root_bytes: TxRootType = [0; TXROOT_LENGTH];
+            BASE64URL_NOPAD.decode_mut(block_index_json_entity.tx_root.as_bytes(), &mut tx_root_bytes).ok()?;
+            Some(tx_root_bytes)
+        };
 
         Some(BlockIndexEntity {
             indep_hash,
             weave_size,
-            tx_root : Some(tx_root),
+            tx_root,
             block_size: weave_size - prev_weave_size,
         })
     }
@@ -330,6 +610,9 @@ impl BlockIndex3 for BlockIndex3Json {
 
     fn get_by_chunk_offset_tx_root(&self, chunk_offset: WeaveOffsetType) -> Option<TxRootType> {
         let BlockJsonIdxRet{idx:_idx, block_index_json_entity} = self._get_block_idx_by_chunk_offset(chunk_offset)?;
+        if block_index_json_entity.tx_root.is_empty() {
+            return None;
+        }
         let mut tx_root: TxRootType = [0; TXROOT_LENGTH];
         BASE64URL_NOPAD.decode_mut(block_index_json_entity.tx_root.as_bytes(), &mut tx_root).ok()?;
         Some(tx_root)
@@ -349,6 +632,22 @@ impl BlockIndex3 for BlockIndex3Json {
         let BlockJsonIdxRet{idx:_idx, block_index_json_entity} = self._get_block_idx_by_chunk_offset(chunk_offset)?;
         Some(block_index_json_entity.tx_root.clone())
     }
+
+    fn locate_offset(&self, chunk_offset: WeaveOffsetType) -> Option<BlockLocation> {
+        let BlockJsonIdxRet{idx, block_index_json_entity: _} = self._get_block_idx_by_chunk_offset(chunk_offset)?;
+        let height = (self.block_list.len() - 1 - idx) as HeightType;
+        Some(block_location(height, self.get_by_height_full(height)?))
+    }
+}
+
+impl BlockIndex3Reverse for BlockIndex3Json {
+    fn get_height_by_indep_hash(&self, indep_hash: &IndepHashType) -> Option<HeightType> {
+        self.reverse_map.get_height_by_indep_hash(indep_hash)
+    }
+
+    fn get_heights_by_tx_root(&self, tx_root: &TxRootType) -> Vec<HeightType> {
+        self.reverse_map.get_heights_by_tx_root(tx_root)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,313 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use futures::future::join_all;
+
+use types::*;
+
+use crate::{fetch_peer_height, BlockIndex3Json, BlockIndexError, DownloadConfig, DownloadRes};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  PeerManager
+//  purpose - peer list for download and chunk fetchers instead of static url list
+//  bootstraps from seed peers, expands via /peers (arweave answers ["ip:port", ...]),
+//  non-public addresses from /peers are dropped by default
+//  probes /info for height and latency
+//  rank: fresh (lag behind best known height <= max_lag) first, then reliability
+//  (successes / attempts, probes and reported fetches both count), then latency
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub struct PeerManagerConfig {
+    // known peers are not expanded beyond this count, seeds always stay
+    pub max_peer_count: usize,
+    // /peers rounds in discover, 1 means only peers of already known peers
+    pub discover_depth: usize,
+    // max blocks behind best known height for peer to be fresh
+    pub max_lag: HeightType,
+    pub timeout: Duration,
+    // peers learned from /peers must be public ip, not loopback / private / link-local (169.254.169.254)
+    // host names are rejected too, arweave lists ip:port; seeds are exempt
+    pub reject_private_peers: bool,
+}
+impl Default for PeerManagerConfig {
+    fn default() -> Self {
+        PeerManagerConfig {
+            max_peer_count: 100,
+            discover_depth: 2,
+            max_lag: 5,
+            timeout: Duration::from_secs(10),
+            reject_private_peers: true,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PeerStat {
+    pub peer_url: String,
+    // from last successful /info probe
+    pub height: Option<HeightType>,
+    pub latency: Option<Duration>,
+    pub success_count: u32,
+    pub failure_count: u32,
+}
+impl PeerStat {
+    fn new(peer_url: String) -> Self {
+        PeerStat {
+            peer_url,
+            height: None,
+            latency: None,
+            success_count: 0,
+            failure_count: 0,
+        }
+    }
+
+    // smoothed, new peer is 0.5
+    pub fn reliability(&self) -> f64 {
+        (self.success_count as f64 + 1.0) / ((self.success_count + self.failure_count) as f64 + 2.0)
+    }
+}
+
+// "1.2.3.4:1984" -> "http://1.2.3.4:1984", None for garbage
+fn normalize_peer_url(peer: &str) -> Option<String> {
+    let peer = peer.trim().trim_end_matches('/');
+    let host_port = peer.strip_prefix("http://").or_else(|| peer.strip_prefix("https://")).unwrap_or(peer);
+    if host_port.is_empty() || !host_port.chars().all(|c| c.is_ascii_alphanumeric() || ".-_:[]".contains(c)) {
+        return None;
+    }
+    if peer.starts_with("http://") || peer.starts_with("https://") {
+        Some(peer.to_string())
+    } else {
+        Some(format!("http://{}", peer))
+    }
+}
+
+// peer_url is normalized, false for host names
+fn is_public_peer_url(peer_url: &str) -> bool {
+    let host = match reqwest::Url::parse(peer_url) {
+        Ok(url) => url.host_str().map(|v| v.trim_start_matches('[').trim_end_matches(']').to_string()),
+        Err(_) => None,
+    };
+    match host.and_then(|host| host.parse::<IpAddr>().ok()) {
+        Some(ip) => is_public_ip(ip),
+        None => false,
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            // 0.0.0.0/8 this network, 100.64.0.0/10 carrier-grade nat
+            !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast()
+                || ip.is_documentation() || ip.is_multicast() || octets[0] == 0 || (octets[0] == 100 && octets[1] & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(ip));
+            }
+            let segment0 = ip.segments()[0];
+            // fc00::/7 unique local, fe80::/10 link-local
+            !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() || segment0 & 0xfe00 == 0xfc00 || segment0 & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+async fn fetch_peer_list(client: &reqwest::Client, peer_url: &str) -> Result<Vec<String>, BlockIndexError> {
+    let url = format!("{}/peers", peer_url);
+    let bytes = client.get(&url).send().await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| BlockIndexError::http(&url, e))?
+        .bytes().await
+        .map_err(|e| BlockIndexError::http(&url, e))?;
+    let peer_list: Vec<String> = serde_json::from_slice(&bytes).map_err(|e| BlockIndexError::http(&url, e))?;
+    Ok(peer_list.iter().filter_map(|v| normalize_peer_url(v)).collect())
+}
+
+pub struct PeerManager {
+    config: PeerManagerConfig,
+    client: reqwest::Client,
+    // insertion order, seeds first
+    peer_list: Vec<PeerStat>,
+    peer_idx_map: HashMap<String, usize>,
+}
+impl PeerManager {
+    pub fn new(seed_peer_url_list: &[String], config: PeerManagerConfig) -> Result<Self, BlockIndexError> {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| BlockIndexError::http("", e))?;
+        let mut ret = PeerManager {
+            config,
+            client,
+            peer_list: Vec::new(),
+            peer_idx_map: HashMap::new(),
+        };
+        for peer_url in seed_peer_url_list {
+            let peer_url = normalize_peer_url(peer_url)
+                .ok_or_else(|| BlockIndexError::InvalidArgument(format!("bad seed peer {}", peer_url)))?;
+            ret._add(peer_url);
+        }
+        if ret.peer_list.is_empty() {
+            return Err(BlockIndexError::NoPeer);
+        }
+        Ok(ret)
+    }
+
+    // false if already known
+    fn _add(&mut self, peer_url: String) -> bool {
+        if self.peer_idx_map.contains_key(&peer_url) {
+            return false;
+        }
+        self.peer_idx_map.insert(peer_url.clone(), self.peer_list.len());
+        self.peer_list.push(PeerStat::new(peer_url));
+        true
+    }
+
+    fn _stat_mut(&mut self, peer_url: &str) -> Option<&mut PeerStat> {
+        let idx = *self.peer_idx_map.get(peer_url)?;
+        self.peer_list.get_mut(idx)
+    }
+
+    pub fn len(&self) -> usize {
+        self.peer_list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peer_list.is_empty()
+    }
+
+    pub fn get(&self, peer_url: &str) -> Option<&PeerStat> {
+        self.peer_list.get(*self.peer_idx_map.get(peer_url)?)
+    }
+
+    // Asks /peers of known peers, discover_depth rounds, each round asks only peers found in previous one
+    // Returns count of new peers
+    pub async fn discover(&mut self) -> usize {
+        let mut asked: HashSet<String> = HashSet::new();
+        let mut ask_list: Vec<String> = self.peer_list.iter().map(|v| v.peer_url.clone()).collect();
+        let mut added_count = 0;
+        for _ in 0..self.config.discover_depth {
+            if ask_list.is_empty() || self.peer_list.len() >= self.config.max_peer_count {
+                break;
+            }
+            asked.extend(ask_list.iter().cloned());
+            let client = &self.client;
+            let res_list = join_all(ask_list.iter().map(|peer_url| fetch_peer_list(client, peer_url))).await;
+            let mut next_ask_list = Vec::new();
+            for (peer_url, res) in ask_list.iter().zip(res_list) {
+                let peer_list = match res {
+                    Ok(peer_list) => peer_list,
+                    Err(_) => {
+                        self.report_failure(peer_url);
+                        continue;
+                    }
+                };
+                for new_peer_url in peer_list {
+                    if self.peer_list.len() >= self.config.max_peer_count {
+                        break;
+                    }
+                    if self.config.reject_private_peers && !is_public_peer_url(&new_peer_url) {
+                        continue;
+                    }
+                    if self._add(new_peer_url.clone()) {
+                        added_count += 1;
+                    }
+                    if !asked.contains(&new_peer_url) && !next_ask_list.contains(&new_peer_url) {
+                        next_ask_list.push(new_peer_url);
+                    }
+                }
+            }
+            ask_list = next_ask_list;
+        }
+        added_count
+    }
+
+    // Probes /info of every known peer concurrently, updates height, latency and counters
+    pub async fn probe(&mut self) {
+        let client = &self.client;
+        let res_list = join_all(self.peer_list.iter().map(|peer| async move {
+            let start = Instant::now();
+            let res = fetch_peer_height(client, &peer.peer_url).await;
+            (res, start.elapsed())
+        })).await;
+        for (peer, (res, latency)) in self.peer_list.iter_mut().zip(res_list) {
+            match res {
+                Ok(height) => {
+                    peer.height = Some(height);
+                    peer.latency = Some(latency);
+                    peer.success_count += 1;
+                }
+                Err(_) => {
+                    peer.failure_count += 1;
+                }
+            }
+        }
+    }
+
+    // discover + probe
+    pub async fn refresh(&mut self) {
+        self.discover().await;
+        self.probe().await;
+    }
+
+    // Fetchers report outcome of real requests, it moves peer in ranking
+    pub fn report_success(&mut self, peer_url: &str) {
+        if let Some(peer) = self._stat_mut(peer_url) {
+            peer.success_count += 1;
+        }
+    }
+
+    pub fn report_failure(&mut self, peer_url: &str) {
+        if let Some(peer) = self._stat_mut(peer_url) {
+            peer.failure_count += 1;
+        }
+    }
+
+    pub fn best_height(&self) -> Option<HeightType> {
+        self.peer_list.iter().filter_map(|v| v.height).max()
+    }
+
+    // Best first; peers which never answered /info are last
+    pub fn ranked_peer_list(&self) -> Vec<&PeerStat> {
+        let best_height = self.best_height().unwrap_or(0);
+        let max_lag = self.config.max_lag;
+        let mut ret: Vec<&PeerStat> = self.peer_list.iter().collect();
+        // 0 fresh, 1 lagging, 2 never answered /info
+        let class = |peer: &PeerStat| match peer.height {
+            Some(height) if best_height - height <= max_lag => 0,
+            Some(_) => 1,
+            None => 2,
+        };
+        // sort is stable, ties keep insertion order
+        ret.sort_by(|a, b| {
+            class(a).cmp(&class(b))
+                .then_with(|| b.reliability().total_cmp(&a.reliability()))
+                .then_with(|| a.latency.unwrap_or(Duration::MAX).cmp(&b.latency.unwrap_or(Duration::MAX)))
+        });
+        ret
+    }
+
+    pub fn ranked_peer_url_list(&self) -> Vec<String> {
+        self.ranked_peer_list().into_iter().map(|v| v.peer_url.clone()).collect()
+    }
+}
+
+impl BlockIndex3Json {
+    // download over ranked peers, reports outcome of every tried peer to peer_manager
+    pub async fn download_ranked(&mut self, peer_manager: &mut PeerManager, config: &DownloadConfig) -> Result<DownloadRes, BlockIndexError> {
+        let res = self.download_with_config(&peer_manager.ranked_peer_url_list(), config).await;
+        let peer_res_list = match &res {
+            Ok(res) => &res.peer_res_list,
+            Err(BlockIndexError::AllPeersFailed(err)) => &err.peer_res_list,
+            Err(_) => return res,
+        };
+        for peer_res in peer_res_list {
+            if peer_res.ok {
+                peer_manager.report_success(&peer_res.peer_url);
+            } else {
+                peer_manager.report_failure(&peer_res.peer_url);
+            }
+        }
+        res
+    }
+}
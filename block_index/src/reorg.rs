@@ -0,0 +1,83 @@
+use types::*;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  Reorg
+//  purpose - replace local tail with other branch, report what was orphaned
+//  so chunk caches can evict data tied to orphaned tx_roots
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(PartialEq, Debug)]
+pub struct OrphanedBlock {
+    pub height: HeightType,
+    pub indep_hash: IndepHashType,
+    pub tx_root: Option<TxRootType>,
+    // chunk offsets in (weave_start, weave_end] belonged to this block
+    pub weave_start: WeaveOffsetType,
+    pub weave_end: WeaveOffsetType,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct ReorgRes {
+    // last height both indexes agree on
+    pub fork_height: HeightType,
+    // oldest first, heights fork_height + 1 ..= old tip
+    pub orphaned_list: Vec<OrphanedBlock>,
+    // chunk offsets in (invalidated_weave_start, invalidated_weave_end] are not confirmed anymore
+    // empty range if nothing was orphaned
+    pub invalidated_weave_start: WeaveOffsetType,
+    pub invalidated_weave_end: WeaveOffsetType,
+    // blocks taken from other branch, heights fork_height + 1 ..= new tip
+    pub applied_count: usize,
+}
+
+// Highest height with same indep_hash in both indexes
+// indep_hash commits to whole history, so binary search is enough
+// None if indexes do not share genesis
+pub fn find_fork_height(a: &dyn BlockIndex3, b: &dyn BlockIndex3) -> Option<HeightType> {
+    let tip = std::cmp::min(a.get_tip_height()?, b.get_tip_height()?);
+    let same = |height: HeightType| a.get_by_height_indep_hash(height) == b.get_by_height_indep_hash(height);
+    if !same(0) {
+        return None;
+    }
+
+    let mut lo: HeightType = 0;
+    let mut hi: HeightType = tip;
+    while lo < hi {
+        let mid = lo + (hi - lo).div_ceil(2);
+        if same(mid) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    Some(lo)
+}
+
+// Blocks of index above fork_height, oldest first
+pub(crate) fn orphaned_list(index: &dyn BlockIndex3, fork_height: HeightType) -> Vec<OrphanedBlock> {
+    let tip = match index.get_tip_height() {
+        Some(tip) => tip,
+        None => return Vec::new(),
+    };
+    (fork_height + 1..=tip).filter_map(|height| {
+        let entity = index.get_by_height_full(height)?;
+        Some(OrphanedBlock {
+            height,
+            indep_hash: entity.indep_hash,
+            tx_root: entity.tx_root,
+            weave_start: entity.weave_size - entity.block_size,
+            weave_end: entity.weave_size,
+        })
+    }).collect()
+}
+
+pub(crate) fn reorg_res(fork_height: HeightType, orphaned_list: Vec<OrphanedBlock>, fork_weave_size: WeaveSizeType, applied_count: usize) -> ReorgRes {
+    let invalidated_weave_end = orphaned_list.last().map(|v| v.weave_end).unwrap_or(fork_weave_size);
+    ReorgRes {
+        fork_height,
+        orphaned_list,
+        invalidated_weave_start: fork_weave_size,
+        invalidated_weave_end,
+        applied_count,
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use types::*;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  ReverseMap
+//  purpose - indep_hash -> height and tx_root -> heights for BlockIndex3Reverse
+//  every backend keeps one, filled on load and patched on append / reorg
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone)]
+pub(crate) struct ReverseMap {
+    indep_hash_map: HashMap<IndepHashType, HeightType>,
+    // heights are ascending
+    tx_root_map: HashMap<TxRootType, Vec<HeightType>>,
+}
+impl Default for ReverseMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl ReverseMap {
+    pub fn new() -> Self {
+        ReverseMap {
+            indep_hash_map: HashMap::new(),
+            tx_root_map: HashMap::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        ReverseMap {
+            indep_hash_map: HashMap::with_capacity(capacity),
+            tx_root_map: HashMap::with_capacity(capacity),
+        }
+    }
+
+    // heights must be inserted in ascending order
+    // indep_hash is unique in valid chain; if not, highest height wins
+    pub fn insert(&mut self, height: HeightType, indep_hash: IndepHashType, tx_root: Option<TxRootType>) {
+        self.indep_hash_map.insert(indep_hash, height);
+        if let Some(tx_root) = tx_root {
+            self.tx_root_map.entry(tx_root).or_default().push(height);
+        }
+    }
+
+    // no-op for entries which were not inserted for this height
+    pub fn remove(&mut self, height: HeightType, indep_hash: &IndepHashType, tx_root: Option<&TxRootType>) {
+        if self.indep_hash_map.get(indep_hash) == Some(&height) {
+            self.indep_hash_map.remove(indep_hash);
+        }
+        if let Some(tx_root) = tx_root {
+            if let Some(height_list) = self.tx_root_map.get_mut(tx_root) {
+                height_list.retain(|v| *v != height);
+                if height_list.is_empty() {
+                    self.tx_root_map.remove(tx_root);
+                }
+            }
+        }
+    }
+
+    pub fn get_height_by_indep_hash(&self, indep_hash: &IndepHashType) -> Option<HeightType> {
+        self.indep_hash_map.get(indep_hash).copied()
+    }
+
+    pub fn get_heights_by_tx_root(&self, tx_root: &TxRootType) -> Vec<HeightType> {
+        self.tx_root_map.get(tx_root).cloned().unwrap_or_default()
+    }
+}
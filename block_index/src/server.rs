@@ -0,0 +1,169 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use data_encoding::BASE64URL_NOPAD;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use hyper::body::Bytes;
+use serde_json::json;
+
+use types::*;
+
+use crate::{BlockIndex3Json, BlockIndexError, BLOCK_INDEX_RANGE_MAX};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  BlockIndexServer
+//  purpose - answer block index lookups over http for tools which do not link this crate
+//  GET /height/{h}, /offset/{o}, /hash/{indep_hash} -> json with *_orig strings
+//  GET /block_index, /block_index/{from}/{to}, /info -> same format as arweave node,
+//  so server works as trusted mirror for download / sync
+//  index is immutable while serving, full /block_index body is serialized once
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub struct BlockIndexServer {
+    index: BlockIndex3Json,
+    block_index_body: Bytes,
+}
+
+fn json_response(status: StatusCode, body: impl Into<Body>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(body.into())
+        .unwrap()
+}
+
+fn error_response(status: StatusCode, err: &str) -> Response<Body> {
+    json_response(status, json!({ "error": err }).to_string())
+}
+
+// {"height", "indep_hash", "weave_size", "tx_root"}, values are *_orig strings
+// Shared with command line tools, so both print same shape
+pub fn height_lookup_json(index: &dyn BlockIndex3, height: HeightType) -> Option<serde_json::Value> {
+    Some(json!({
+        "height": height,
+        "indep_hash": index.get_by_height_indep_hash_orig(height)?,
+        "weave_size": index.get_by_height_weave_size_orig(height)?,
+        "tx_root": index.get_by_height_tx_root_orig(height)?,
+    }))
+}
+
+// height_lookup_json of block owning offset + "block_start", "block_end", "block_size"
+// big numbers are decimal strings, same as weave_size
+pub fn offset_lookup_json(index: &dyn BlockIndex3, offset: WeaveOffsetType) -> Option<serde_json::Value> {
+    let location = index.locate_offset(offset)?;
+    let mut block = height_lookup_json(index, location.height)?;
+    block["block_start"] = location.block_start.to_string().into();
+    block["block_end"] = location.block_end.to_string().into();
+    block["block_size"] = location.block_size.to_string().into();
+    Some(block)
+}
+
+impl BlockIndexServer {
+    pub fn new(index: BlockIndex3Json) -> Result<Self, BlockIndexError> {
+        if index.block_list.is_empty() {
+            return Err(BlockIndexError::NotLoaded);
+        }
+        let block_index_body = Bytes::from(serde_json::to_vec(&index.block_list)?);
+        Ok(BlockIndexServer { index, block_index_body })
+    }
+
+    pub fn index(&self) -> &BlockIndex3Json {
+        &self.index
+    }
+
+    // Binds addr (port 0 picks free one), returns bound addr and future which serves until error
+    pub fn serve(self: Arc<Self>, addr: SocketAddr) -> Result<(SocketAddr, impl Future<Output = Result<(), BlockIndexError>>), BlockIndexError> {
+        let server = hyper::Server::try_bind(&addr).map_err(|e| BlockIndexError::http(&addr.to_string(), e))?;
+        let make_svc = hyper::service::make_service_fn(move |_conn| {
+            let this = self.clone();
+            async move {
+                Ok::<_, hyper::Error>(hyper::service::service_fn(move |req| {
+                    let res = this.handle(&req);
+                    async move { Ok::<_, hyper::Error>(res) }
+                }))
+            }
+        });
+        let server = server.serve(make_svc);
+        let local_addr = server.local_addr();
+        let fut = async move {
+            server.await.map_err(|e| BlockIndexError::http(&local_addr.to_string(), e))
+        };
+        Ok((local_addr, fut))
+    }
+
+    pub fn handle(&self, req: &Request<Body>) -> Response<Body> {
+        if req.method() != Method::GET {
+            return error_response(StatusCode::METHOD_NOT_ALLOWED, "only GET is supported");
+        }
+        let path: Vec<&str> = req.uri().path().split('/').filter(|v| !v.is_empty()).collect();
+        match path.as_slice() {
+            ["info"] => json_response(StatusCode::OK, json!({ "height": self._tip_height() }).to_string()),
+            ["block_index"] => json_response(StatusCode::OK, self.block_index_body.clone()),
+            ["block_index", from, to] => self._block_index_range(from, to),
+            ["height", height] => match height.parse::<HeightType>() {
+                Ok(height) => self._block_response(height),
+                Err(_) => error_response(StatusCode::BAD_REQUEST, "height is not decimal"),
+            },
+            ["offset", offset] => match offset.parse::<WeaveOffsetType>() {
+                Ok(offset) => self._offset_response(offset),
+                Err(_) => error_response(StatusCode::BAD_REQUEST, "offset is not decimal"),
+            },
+            ["hash", indep_hash] => self._hash_response(indep_hash),
+            _ => error_response(StatusCode::NOT_FOUND, "not found"),
+        }
+    }
+
+    fn _tip_height(&self) -> HeightType {
+        // not empty, checked in new
+        self.index.get_tip_height().unwrap()
+    }
+
+    fn _block_response(&self, height: HeightType) -> Response<Body> {
+        // checked before lookup, height near HeightType::MAX overflows index math
+        if height > self._tip_height() {
+            return error_response(StatusCode::NOT_FOUND, "height is above tip");
+        }
+        match height_lookup_json(&self.index, height) {
+            Some(block) => json_response(StatusCode::OK, block.to_string()),
+            None => error_response(StatusCode::NOT_FOUND, "height is above tip"),
+        }
+    }
+
+    fn _offset_response(&self, offset: WeaveOffsetType) -> Response<Body> {
+        match offset_lookup_json(&self.index, offset) {
+            Some(block) => json_response(StatusCode::OK, block.to_string()),
+            None => error_response(StatusCode::NOT_FOUND, "offset is not in block index"),
+        }
+    }
+
+    fn _hash_response(&self, indep_hash: &str) -> Response<Body> {
+        let indep_hash: IndepHashType = match BASE64URL_NOPAD.decode(indep_hash.as_bytes()).ok().and_then(|v| v.try_into().ok()) {
+            Some(indep_hash) => indep_hash,
+            None => return error_response(StatusCode::BAD_REQUEST, "indep_hash is not base64url of 48 bytes"),
+        };
+        match self.index.get_height_by_indep_hash(&indep_hash) {
+            Some(height) => self._block_response(height),
+            None => error_response(StatusCode::NOT_FOUND, "indep_hash is not in block index"),
+        }
+    }
+
+    // blocks at heights from..=to, newest first
+    fn _block_index_range(&self, from: &str, to: &str) -> Response<Body> {
+        let (from, to) = match (from.parse::<HeightType>(), to.parse::<HeightType>()) {
+            (Ok(from), Ok(to)) if from <= to => (from, to),
+            _ => return error_response(StatusCode::BAD_REQUEST, "bad range"),
+        };
+        if to - from > BLOCK_INDEX_RANGE_MAX {
+            return error_response(StatusCode::BAD_REQUEST, "range is too big");
+        }
+        if to > self._tip_height() {
+            return error_response(StatusCode::NOT_FOUND, "range is above tip");
+        }
+        let len = self.index.block_list.len();
+        let block_list = &self.index.block_list[len - 1 - to as usize..len - from as usize];
+        match serde_json::to_string(block_list) {
+            Ok(body) => json_response(StatusCode::OK, body),
+            Err(err) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &err.to_string()),
+        }
+    }
+}
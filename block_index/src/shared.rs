@@ -0,0 +1,186 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use arc_swap::{ArcSwap, Guard};
+use futures::future::join_all;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use types::*;
+
+use crate::{fetch_peer_height, BlockIndex3Json, BlockIndexError, CancellationToken, DownloadConfig};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  SharedBlockIndex
+//  purpose - one block index for many threads / tasks
+//  readers take immutable snapshot (atomic pointer load, no lock), snapshot never changes
+//  writers build next index aside (copy, sync) and swap pointer, old snapshots live on
+//  until last reader drops them
+//  tip change is published as NewTip to subscribers
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+// buffered events per subscriber, slow subscriber gets RecvError::Lagged
+const NEW_TIP_CHANNEL_CAPACITY: usize = 64;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct NewTip {
+    pub prev_tip_height: Option<HeightType>,
+    pub height: HeightType,
+    pub indep_hash: IndepHashType,
+    pub weave_size: WeaveSizeType,
+}
+
+pub struct UpdaterConfig {
+    // between end of one update and start of next
+    pub interval: Duration,
+    pub download: DownloadConfig,
+}
+impl Default for UpdaterConfig {
+    fn default() -> Self {
+        UpdaterConfig {
+            interval: Duration::from_secs(60),
+            download: DownloadConfig::default(),
+        }
+    }
+}
+
+pub struct SharedBlockIndex {
+    current: ArcSwap<BlockIndex3Json>,
+    new_tip_tx: broadcast::Sender<NewTip>,
+    last_update_err: Mutex<Option<String>>,
+}
+
+fn tip_of(index: &BlockIndex3Json) -> Option<(HeightType, IndepHashType, WeaveSizeType)> {
+    let height = index.get_tip_height()?;
+    Some((height, index.get_by_height_indep_hash(height)?, index.get_by_height_weave_size(height)?))
+}
+
+impl SharedBlockIndex {
+    pub fn new(index: BlockIndex3Json) -> Self {
+        let (new_tip_tx, _) = broadcast::channel(NEW_TIP_CHANNEL_CAPACITY);
+        SharedBlockIndex {
+            current: ArcSwap::from_pointee(index),
+            new_tip_tx,
+            last_update_err: Mutex::new(None),
+        }
+    }
+
+    // Cheapest read, for short lookups; holding many guards for long falls back to slower path
+    pub fn load(&self) -> Guard<Arc<BlockIndex3Json>> {
+        self.current.load()
+    }
+
+    // Owned snapshot, for long reads and across await
+    pub fn snapshot(&self) -> Arc<BlockIndex3Json> {
+        self.current.load_full()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<NewTip> {
+        self.new_tip_tx.subscribe()
+    }
+
+    // Last error of background updater, None after successful update
+    pub fn last_update_err(&self) -> Option<String> {
+        self.last_update_err.lock().unwrap().clone()
+    }
+
+    // Swaps unconditionally, publishes NewTip if tip indep_hash changed
+    pub fn replace(&self, index: BlockIndex3Json) {
+        let next = Arc::new(index);
+        let prev = self.current.swap(next.clone());
+        self._publish(&prev, &next);
+    }
+
+    fn _publish(&self, prev: &BlockIndex3Json, next: &BlockIndex3Json) -> Option<NewTip> {
+        let (height, indep_hash, weave_size) = tip_of(next)?;
+        let prev_tip = tip_of(prev);
+        if prev_tip.map(|v| v.1) == Some(indep_hash) {
+            return None;
+        }
+        let new_tip = NewTip {
+            prev_tip_height: prev_tip.map(|v| v.0),
+            height,
+            indep_hash,
+            weave_size,
+        };
+        // Err only means nobody is subscribed
+        let _ = self.new_tip_tx.send(new_tip.clone());
+        Some(new_tip)
+    }
+
+    // One update round: if some peer is above local tip, syncs copy of current snapshot
+    // and swaps it in; peer on other branch is downloaded in full and reorged onto
+    // only if its tip is above local tip, otherwise current is kept
+    // Swap is skipped if current was replaced meanwhile, next round retries
+    // Returns published NewTip
+    pub async fn update(&self, peer_url_list: &[String], config: &DownloadConfig) -> Result<Option<NewTip>, BlockIndexError> {
+        let current = self.snapshot();
+        if !current.block_list.is_empty() && !self._any_peer_above(peer_url_list, config, &current).await? {
+            return Ok(None);
+        }
+        let mut next = (*current).clone();
+        match next.sync_with_config(peer_url_list, config).await {
+            Ok(_) => {}
+            Err(BlockIndexError::NotAncestor { url, height }) => {
+                // url is request url, reorg only onto branch of peer which reported it
+                let peer_url = peer_url_list.iter()
+                    .find(|peer_url| url.starts_with(&format!("{}/block_index", peer_url)))
+                    .ok_or(BlockIndexError::NotAncestor { url: url.clone(), height })?;
+                let mut other = BlockIndex3Json::new();
+                other.set_checkpoint_list(next.checkpoint_list().cloned());
+                other.download_with_config(std::slice::from_ref(peer_url), config).await?;
+                if other.get_tip_height() <= current.get_tip_height() {
+                    return Ok(None);
+                }
+                next.reorg(&other)?;
+            }
+            Err(err) => return Err(err),
+        }
+        let next = Arc::new(next);
+        let prev = self.current.compare_and_swap(&current, next.clone());
+        if !Arc::ptr_eq(&prev, &current) {
+            return Ok(None);
+        }
+        Ok(self._publish(&current, &next))
+    }
+
+    // Err if no peer answered /info
+    async fn _any_peer_above(&self, peer_url_list: &[String], config: &DownloadConfig, current: &BlockIndex3Json) -> Result<bool, BlockIndexError> {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| BlockIndexError::http("", e))?;
+        let tip_height = current.get_tip_height().unwrap_or(0);
+        let mut last_err = BlockIndexError::NoPeer;
+        let mut answered = false;
+        for res in join_all(peer_url_list.iter().map(|peer_url| fetch_peer_height(&client, peer_url))).await {
+            match res {
+                Ok(height) if height > tip_height => return Ok(true),
+                Ok(_) => answered = true,
+                Err(err) => last_err = err,
+            }
+        }
+        if answered {
+            return Ok(false);
+        }
+        Err(last_err)
+    }
+
+    // Runs update every config.interval until cancel, must be called within tokio runtime
+    // Errors are kept in last_update_err, updater goes on
+    pub fn spawn_updater(self: &Arc<Self>, peer_url_list: Vec<String>, config: UpdaterConfig, cancel: CancellationToken) -> JoinHandle<()> {
+        let this = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let res = tokio::select! {
+                    _ = cancel.cancelled() => return,
+                    res = this.update(&peer_url_list, &config.download) => res,
+                };
+                *this.last_update_err.lock().unwrap() = res.err().map(|err| err.to_string());
+                tokio::select! {
+                    _ = cancel.cancelled() => return,
+                    _ = tokio::time::sleep(config.interval) => {}
+                }
+            }
+        })
+    }
+}
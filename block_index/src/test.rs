@@ -3,7 +3,7 @@ mod block_index_test {
     use crate::*;
 
     use std::fs::File;
-    use std::io::Read;
+    use std::io::{Read, Write};
     use std::path::PathBuf;
     use tokio::runtime::Runtime;
     use std::future::Future;
@@ -53,11 +53,30 @@ mod block_index_test {
     static INDEX: Lazy<BlockIndex3Json> = Lazy::new(|| {
         let path = "../test_asset/block_index_slice";
         let mut index = BlockIndex3Json::new();
-        index.load_sync(&path).unwrap();
+        index.load_sync(path).unwrap();
         index
     });
 
-    fn run_test<T: Future<Output = Result<(), Box<dyn std::error::Error>>>>(fut: T) -> Result<(), Box<dyn std::error::Error>> {
+    static INDEX_BIN: Lazy<BlockIndex3Bin> = Lazy::new(|| {
+        let path = std::env::temp_dir().join(format!("block_index_slice_bin_{}", std::process::id()));
+        BlockIndex3Bin::from_json_sync(&INDEX, path.to_str().unwrap()).unwrap()
+    });
+
+    static INDEX_DECODED: Lazy<BlockIndex3Decoded> = Lazy::new(|| {
+        let path = "../test_asset/block_index_slice";
+        let mut index = BlockIndex3Decoded::new();
+        index.load_sync(path).unwrap();
+        index
+    });
+
+    // Connection is not Sync, no static
+    fn index_sqlite() -> BlockIndex3Sqlite {
+        let mut index = BlockIndex3Sqlite::open_in_memory().unwrap();
+        index.import_json(&INDEX).unwrap();
+        index
+    }
+
+    fn run_test<R, T: Future<Output = Result<R, BlockIndexError>>>(fut: T) -> Result<R, BlockIndexError> {
         let rt = Runtime::new()?;
         rt.block_on(fut)
     }
@@ -66,7 +85,7 @@ mod block_index_test {
    fn test_load() -> Result<(), Box<dyn std::error::Error>> {
        let path = "../test_asset/block_index_slice";
        let mut index = BlockIndex3Json::new();
-       run_test(index.load(&path))
+       Ok(run_test(index.load(path))?)
    }
 
     #[test]
@@ -74,13 +93,13 @@ mod block_index_test {
         let path = "../test_asset/block_index_slice";
         let target_file = "../test_asset/block_index_slice_re";
         let mut index = BlockIndex3Json::new();
-        run_test(index.load(&path))?;
+        run_test(index.load(path))?;
 
         if PathBuf::from(target_file).exists() {
             std::fs::remove_file(target_file)?;
         }
 
-        run_test(index.save(&target_file))?;
+        run_test(index.save(target_file))?;
         assert!(PathBuf::from(target_file).exists());
 
         let mut buf1 = Vec::new();
@@ -98,7 +117,7 @@ mod block_index_test {
    fn test_load_sync() -> Result<(), Box<dyn std::error::Error>> {
        let path = "../test_asset/block_index_slice";
        let mut index = BlockIndex3Json::new();
-       index.load_sync(&path)?;
+       index.load_sync(path)?;
        Ok(())
    }
 
@@ -108,13 +127,13 @@ mod block_index_test {
         // NOTE. DO NOT USE block_index_slice_re, rust tests will run in parallel and will conflict for shared file
         let target_file = "../test_asset/block_index_slice_re2";
         let mut index = BlockIndex3Json::new();
-        run_test(index.load(&path))?;
+        run_test(index.load(path))?;
 
         if PathBuf::from(target_file).exists() {
             std::fs::remove_file(target_file)?;
         }
 
-        index.save_sync(&target_file)?;
+        index.save_sync(target_file)?;
         assert!(PathBuf::from(target_file).exists());
 
         let mut buf1 = Vec::new();
@@ -158,7 +177,7 @@ mod block_index_test {
             let result = run_test(index.download(&[peer_url]));
 
             match result {
-                Ok(()) => {
+                Ok(_) => {
                     assert_eq!(index.block_list[4307], *BLOCK_0_ORIG);
                     assert_eq!(index.block_list[0], *BLOCK_4307_ORIG);
                 },
@@ -171,10 +190,10 @@ mod block_index_test {
             let peer_url = format!("http://{}", addr);
 
             let mut index = BlockIndex3Json::new();
-            let result = run_test(index.download(&["http://127.0.0.1:1338".into(), peer_url]));
+            let result = run_test(index.download_with_config(&["http://127.0.0.1:1338".into(), peer_url], &fast_download_config()));
 
             match result {
-                Ok(()) => {
+                Ok(_) => {
                     assert_eq!(index.block_list[4307], *BLOCK_0_ORIG);
                     assert_eq!(index.block_list[0], *BLOCK_4307_ORIG);
                 },
@@ -185,10 +204,10 @@ mod block_index_test {
         }
         {
             let mut index = BlockIndex3Json::new();
-            let result = run_test(index.download(&["http://127.0.0.1:1338".into(), "http://127.0.0.1:1339".into()]));
+            let result = run_test(index.download_with_config(&["http://127.0.0.1:1338".into(), "http://127.0.0.1:1339".into()], &fast_download_config()));
 
             match result {
-                Ok(()) => {
+                Ok(_) => {
                     panic!("Download should fail");
                 },
                 Err(_err) => {}
@@ -198,6 +217,129 @@ mod block_index_test {
         Ok(())
     }
 
+    // same retries, backoff is short so tests do not sleep
+    fn fast_download_config() -> DownloadConfig {
+        DownloadConfig {
+            backoff_base: std::time::Duration::from_millis(1),
+            backoff_max: std::time::Duration::from_millis(10),
+            ..DownloadConfig::default()
+        }
+    }
+
+    fn spawn_test_server<F>(handler: F) -> std::net::SocketAddr
+    where
+        F: Fn(hyper::Request<hyper::Body>) -> hyper::Response<hyper::Body> + Send + Sync + 'static,
+    {
+        let handler = std::sync::Arc::new(handler);
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(async {
+                let make_svc = hyper::service::make_service_fn(move |_conn| {
+                    let handler = handler.clone();
+                    async move {
+                        Ok::<_, hyper::Error>(hyper::service::service_fn(move |req| {
+                            let res = handler(req);
+                            async move { Ok::<_, hyper::Error>(res) }
+                        }))
+                    }
+                });
+                let addr = ([127, 0, 0, 1], 0).into();
+                let server = hyper::Server::bind(&addr).serve(make_svc);
+                tx.send(server.local_addr()).unwrap();
+                server.await.unwrap();
+            });
+        });
+        rx.recv().unwrap()
+    }
+
+    // serves INDEX as peer with tip 4307
+    fn peer_handler(req: hyper::Request<hyper::Body>, ranged: bool) -> hyper::Response<hyper::Body> {
+        let block_list = &INDEX.block_list;
+        let path: Vec<&str> = req.uri().path().split('/').filter(|v| !v.is_empty()).collect();
+        let body = match path.as_slice() {
+            ["info"] => format!("{{\"height\":{}}}", block_list.len() - 1),
+            ["block_index"] => serde_json::to_string(block_list).unwrap(),
+            ["block_index", from, to] if ranged => {
+                let from: usize = from.parse().unwrap();
+                let to: usize = to.parse().unwrap();
+                serde_json::to_string(&block_list[block_list.len() - 1 - to..block_list.len() - from]).unwrap()
+            }
+            _ => return hyper::Response::builder().status(404).body(hyper::Body::empty()).unwrap(),
+        };
+        hyper::Response::new(hyper::Body::from(body))
+    }
+
+    fn peer_ranged_handler(req: hyper::Request<hyper::Body>) -> hyper::Response<hyper::Body> {
+        peer_handler(req, true)
+    }
+
+    fn peer_full_handler(req: hyper::Request<hyper::Body>) -> hyper::Response<hyper::Body> {
+        peer_handler(req, false)
+    }
+
+    // port was free a moment ago, nothing listens there
+    fn dead_peer_url() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    fn index_without_tip(count: usize) -> BlockIndex3Json {
+        let mut index = BlockIndex3Json::new();
+        index._load_from_original_format(INDEX.block_list[count..].to_vec()).unwrap();
+        index
+    }
+
+    #[test]
+    fn test_sync() -> Result<(), Box<dyn std::error::Error>> {
+        let peer_ranged_url = format!("http://{}", spawn_test_server(peer_ranged_handler));
+        let peer_full_url = format!("http://{}", spawn_test_server(peer_full_handler));
+        let dead_url = dead_peer_url();
+        let rt = Runtime::new()?;
+
+        for peer_url in [&peer_ranged_url, &peer_full_url] {
+            // several BLOCK_INDEX_RANGE_MAX pages
+            for count in [8, 2500] {
+                let mut index = index_without_tip(count);
+                let peer_url_list = [dead_url.clone(), peer_url.clone()];
+                assert_eq!(rt.block_on(index.sync(&peer_url_list))?, count);
+                assert_eq!(index.block_list, INDEX.block_list);
+                assert_eq!(index.get_by_chunk_offset_full(1039029).unwrap(), *BLOCK_I780);
+
+                assert_eq!(rt.block_on(index.sync(&peer_url_list))?, 0);
+                assert_eq!(index.get_height_by_indep_hash(&BLOCK_4307.indep_hash), Some(4307));
+                assert_eq!(index.block_list, INDEX.block_list);
+            }
+
+            // old tip is not an ancestor
+            let mut index = index_without_tip(8);
+            index.block_list[0].hash = BLOCK_0_ORIG.hash.clone();
+            let err = rt.block_on(index.sync(std::slice::from_ref(peer_url))).unwrap_err();
+            assert!(matches!(err, BlockIndexError::NotAncestor { height: 4299, .. }), "{}", err);
+            assert_eq!(index.block_list.len(), 4300);
+        }
+
+        let mut index = BlockIndex3Json::new();
+        assert_eq!(rt.block_on(index.sync(std::slice::from_ref(&peer_ranged_url)))?, 4308);
+        assert_eq!(index.block_list, INDEX.block_list);
+
+        assert!(matches!(rt.block_on(index.sync(std::slice::from_ref(&dead_url))), Err(BlockIndexError::Http { .. })));
+
+        // peer ahead fails, lagging peer must not turn it into up to date
+        let broken_ahead_url = format!("http://{}", spawn_test_server(|req| match req.uri().path() {
+            "/info" => hyper::Response::new(hyper::Body::from("{\"height\":5000}")),
+            _ => hyper::Response::builder().status(500).body(hyper::Body::empty()).unwrap(),
+        }));
+        for peer_url_list in [[peer_ranged_url.clone(), broken_ahead_url.clone()], [broken_ahead_url.clone(), peer_ranged_url.clone()]] {
+            let err = rt.block_on(index.sync(&peer_url_list)).unwrap_err();
+            assert!(err.to_string().starts_with(&format!("{}/block_index", broken_ahead_url)), "{}", err);
+            assert_eq!(index.block_list, INDEX.block_list);
+        }
+        // no peer ahead, dead one is unknown
+        assert_eq!(rt.block_on(index.sync(&[dead_url, peer_ranged_url]))?, 0);
+        Ok(())
+    }
+
     #[test]
     fn get_by_height_full() {
         assert!(INDEX.get_by_height_full(NOT_EXIST_INDEX).is_none());
@@ -252,7 +394,7 @@ mod block_index_test {
         assert_eq!(INDEX._get_block_idx_by_chunk_offset(NOT_EXIST_OFFSET), None);
         assert_eq!(INDEX._get_block_idx_by_chunk_offset(1039029 + 1), None);
 
-        fn fn_test(idx: usize, block_list: &[BlockIndex3JsonEntity]) -> BlockJsonIdxRet {
+        fn fn_test(idx: usize, block_list: &[BlockIndex3JsonEntity]) -> BlockJsonIdxRet<'_> {
             BlockJsonIdxRet {
               idx,
               block_index_json_entity : &block_list[idx]
@@ -309,4 +451,1202 @@ mod block_index_test {
         assert_eq!(INDEX.get_by_chunk_offset_tx_root_orig(1039029).unwrap(), BLOCK_I780_ORIG.tx_root);
     }
 
+    #[test]
+    fn locate_offset() {
+        let index_sqlite = index_sqlite();
+        let backend_list: [&dyn BlockIndex3; 4] = [&*INDEX, &*INDEX_BIN, &*INDEX_DECODED, &index_sqlite];
+        for index in backend_list {
+            assert!(index.locate_offset(NOT_EXIST_OFFSET).is_none());
+            assert!(index.locate_offset(1039029 + 1).is_none());
+            assert_eq!(index.locate_offset(1039029).unwrap(), BlockLocation {
+                height: 3527,
+                block_start: 599058,
+                block_end: 1039029,
+                block_size: 439971,
+                tx_root: BLOCK_I780.tx_root,
+            });
+            assert_eq!(index.locate_offset(599058 + 1).unwrap().height, 3527);
+            assert_eq!(index.locate_offset(1).unwrap().height, 82);
+            assert_eq!(index.locate_offset(599058).unwrap().block_start, 0);
+
+            // heights 3528..=4307 are empty, same weave_size as 3527
+            assert_eq!(BLOCK_4307.weave_size, 1039029);
+            assert_eq!(BLOCK_4307.block_size, 0);
+            assert_eq!(index.locate_offset(BLOCK_4307.weave_size).unwrap().height, 3527);
+
+            // genesis edge
+            assert_eq!(index.locate_offset(0).unwrap(), BlockLocation {
+                height: 0,
+                block_start: 0,
+                block_end: 0,
+                block_size: 0,
+                tx_root: index.get_by_height_tx_root(0),
+            });
+
+            for height in 0..4308 {
+                let weave_size = index.get_by_height_weave_size(height).unwrap();
+                for chunk_offset in [weave_size - 1, weave_size, weave_size + 1] {
+                    let location = index.locate_offset(chunk_offset);
+                    assert_eq!(location, INDEX_DECODED.locate_offset(chunk_offset));
+                    if let Some(location) = location {
+                        assert!(location.block_size > 0 || location.height == 0);
+                        assert_eq!(location.block_end - location.block_start, location.block_size);
+                        assert_eq!(index.get_by_chunk_offset_weave_size(chunk_offset), Some(location.block_end));
+                    }
+                }
+            }
+        }
+
+        let empty = BlockIndex3Json::new();
+        assert!(empty.locate_offset(0).is_none());
+        assert!(empty.locate_offset(1).is_none());
+        assert!(empty.get_by_chunk_offset_weave_size(0).is_none());
+    }
+
+    #[test]
+    fn locate_offset_block_end() {
+        // every block has data, so each weave_size is end offset of exactly one block
+        let block_size_list: [WeaveSizeType; 8] = [7, 100, 3, 50, 1, 1000, 20, 5];
+        for block_count in 1..=block_size_list.len() + 1 {
+            let mut weave_size_list = vec![0];
+            for block_size in &block_size_list[..block_count - 1] {
+                weave_size_list.push(weave_size_list.last().unwrap() + block_size);
+            }
+            let block_json_list: Vec<String> = weave_size_list.iter().rev()
+                .map(|weave_size| format!("{{\"tx_root\":\"\",\"weave_size\":\"{}\",\"hash\":\"{}\"}}", weave_size, "A".repeat(64)))
+                .collect();
+            let mut index = BlockIndex3Json::new();
+            index.load_from_reader(format!("[{}]", block_json_list.join(",")).as_bytes()).unwrap();
+            let path = std::env::temp_dir().join(format!("block_index_block_end_bin_{}", block_count));
+            let index_bin = BlockIndex3Bin::from_json_sync(&index, path.to_str().unwrap()).unwrap();
+            let index_decoded = BlockIndex3Decoded::from_json(&index).unwrap();
+            let mut index_sqlite = BlockIndex3Sqlite::open_in_memory().unwrap();
+            index_sqlite.import_json(&index).unwrap();
+            let backend_list: [&dyn BlockIndex3; 4] = [&index, &index_bin, &index_decoded, &index_sqlite];
+
+            for (height, weave_size) in weave_size_list.iter().enumerate() {
+                let height = height as HeightType;
+                let prev_weave_size = if height == 0 { -1 } else { weave_size_list[height as usize - 1] };
+                let next_height = if (height as usize) + 1 < weave_size_list.len() { Some(height + 1) } else { None };
+                for (chunk_offset, expected_height) in [(*weave_size, Some(height)), (weave_size + 1, next_height), (prev_weave_size + 1, Some(height))] {
+                    for backend in backend_list {
+                        assert_eq!(backend.locate_offset(chunk_offset).map(|v| v.height), expected_height, "block_count {} chunk_offset {}", block_count, chunk_offset);
+                        assert_eq!(backend.locate_offset(chunk_offset), index_decoded.locate_offset(chunk_offset));
+                    }
+                }
+            }
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn iter_heights() {
+        let backend_list: [&dyn BlockIndex3; 3] = [&*INDEX, &*INDEX_BIN, &*INDEX_DECODED];
+        for index in backend_list {
+            let all: Vec<(HeightType, BlockIndexEntity)> = index.iter_heights(..).collect();
+            assert_eq!(all.len(), 4308);
+            for (height, entity) in &all {
+                assert_eq!(Some(entity), index.get_by_height_full(*height).as_ref());
+            }
+            assert_eq!(all[3527].1, *BLOCK_I780);
+            assert_eq!(all[4307].1, *BLOCK_4307);
+
+            let rev: Vec<(HeightType, BlockIndexEntity)> = index.iter_heights_rev(..).collect();
+            assert!(rev.iter().eq(all.iter().rev()));
+
+            let heights = |it: &mut dyn Iterator<Item = (HeightType, BlockIndexEntity)>| it.map(|(height, _)| height).collect::<Vec<_>>();
+            assert_eq!(heights(&mut index.iter_heights(3526..=3528)), vec![3526, 3527, 3528]);
+            assert_eq!(heights(&mut index.iter_heights_rev(3526..3529)), vec![3528, 3527, 3526]);
+            assert_eq!(heights(&mut index.iter_heights(4306..10000)), vec![4306, 4307]);
+            assert!(index.iter_heights(5000..).next().is_none());
+            assert_eq!(index.iter_heights(3527..3528).next().unwrap().1, *BLOCK_I780);
+            assert_eq!(index.iter_heights_rev(..=3527).next().unwrap().1, *BLOCK_I780);
+
+            // both ends meet
+            let mut it = index.iter_heights(3526..3530);
+            assert_eq!(it.size_hint(), (0, Some(4)));
+            assert_eq!(it.next_back().unwrap().0, 3529);
+            assert_eq!(it.next().unwrap().0, 3526);
+            assert_eq!(it.next_back().unwrap().1, index.get_by_height_full(3528).unwrap());
+            assert_eq!(it.next().unwrap().1, *BLOCK_I780);
+            assert!(it.next().is_none());
+            assert!(it.next_back().is_none());
+        }
+        assert!(BlockIndex3Json::new().iter_heights(..).next().is_none());
+
+        // failed lookup ends iteration from both ends
+        let mut corrupt = (*INDEX).clone();
+        let corrupt_idx = corrupt.block_list.len() - 1 - 3527;
+        corrupt.block_list[corrupt_idx].weave_size = "x".into();
+        let mut it = corrupt.iter_heights(3526..3530);
+        assert_eq!(it.next().unwrap().0, 3526);
+        assert!(it.next().is_none());
+        assert_eq!(it.size_hint(), (0, Some(0)));
+        assert!(it.next_back().is_none());
+        let mut it = corrupt.iter_heights_rev(3526..3530);
+        // 3528 needs weave_size of 3527 too
+        assert_eq!(it.next().unwrap().0, 3529);
+        assert!(it.next().is_none());
+        assert!(it.next_back().is_none());
+    }
+
+    #[test]
+    fn iter_offsets() {
+        let backend_list: [&dyn BlockIndex3; 3] = [&*INDEX, &*INDEX_BIN, &*INDEX_DECODED];
+        for index in backend_list {
+            let heights = |weave_range: std::ops::Range<WeaveOffsetType>| index.iter_offsets(weave_range).map(|(height, _)| height).collect::<Vec<_>>();
+            assert_eq!(heights(0..2_000_000), vec![82, 3527]);
+            assert_eq!(heights(1..2), vec![82]);
+            assert_eq!(heights(599058..599059), vec![82]);
+            assert_eq!(heights(599058..599060), vec![82, 3527]);
+            assert_eq!(heights(599059..1039030), vec![3527]);
+            assert!(heights(1039030..2_000_000).is_empty());
+            assert!(heights(-10..0).is_empty());
+            assert!(heights(5..5).is_empty());
+
+            let (_, entity) = index.iter_offsets(1039029..1039030).next().unwrap();
+            assert_eq!(entity, *BLOCK_I780);
+        }
+    }
+
+    #[test]
+    fn reverse_lookup() {
+        let index_sqlite = index_sqlite();
+        let backend_list: [&dyn BlockIndex3Reverse; 4] = [&*INDEX, &*INDEX_BIN, &*INDEX_DECODED, &index_sqlite];
+        for index in backend_list {
+            assert_eq!(index.get_height_by_indep_hash(&BLOCK_I780.indep_hash), Some(3527));
+            assert_eq!(index.get_heights_by_tx_root(&BLOCK_I780.tx_root.unwrap()), vec![3527]);
+            assert_eq!(index.get_height_by_indep_hash(&BLOCK_4307.indep_hash), Some(4307));
+            assert_eq!(index.get_height_by_indep_hash(&[0; INDEPHASH_LENGTH]), None);
+            assert!(index.get_heights_by_tx_root(&[0; TXROOT_LENGTH]).is_empty());
+
+            for height in 0..4308 {
+                let entity = index.get_by_height_full(height).unwrap();
+                assert_eq!(index.get_height_by_indep_hash(&entity.indep_hash), Some(height));
+                if let Some(tx_root) = entity.tx_root {
+                    assert!(index.get_heights_by_tx_root(&tx_root).contains(&height));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bin_json_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let path = "../test_asset/block_index_slice";
+        // NOTE. unique name, see test_save_sync
+        let target_file = "../test_asset/block_index_slice_re3";
+        let index = INDEX_BIN.to_json()?;
+        index.save_sync(target_file)?;
+
+        let mut buf1 = Vec::new();
+        let mut buf2 = Vec::new();
+        File::open(path)?.read_to_end(&mut buf1)?;
+        File::open(target_file)?.read_to_end(&mut buf2)?;
+        assert_eq!(buf1, buf2);
+
+        std::fs::remove_file(target_file)?;
+        Ok(())
+    }
+
+    #[test]
+    fn bin_load_sync() -> Result<(), Box<dyn std::error::Error>> {
+        let target_file = std::env::temp_dir().join(format!("block_index_slice_bin_re_{}", std::process::id()));
+        INDEX_BIN.save_sync(target_file.to_str().unwrap())?;
+        let mut index = BlockIndex3Bin::new();
+        index.load_sync(target_file.to_str().unwrap())?;
+        assert_eq!(index.len(), 4308);
+
+        // save over mapped file, existing map keeps old content
+        index.save_sync(target_file.to_str().unwrap())?;
+        assert_eq!(index.len(), 4308);
+        assert_eq!(index.get_by_height_full(4307).unwrap(), *BLOCK_4307);
+
+        // torn record
+        let mut buf = fs::read(&target_file)?;
+        buf.pop();
+        fs::write(&target_file, &buf)?;
+        assert!(matches!(BlockIndex3Bin::new().load_sync(target_file.to_str().unwrap()), Err(BlockIndexError::Corrupt(_))));
+
+        std::fs::remove_file(target_file)?;
+        Ok(())
+    }
+
+    #[test]
+    fn bin_get_by_height() {
+        assert!(INDEX_BIN.get_by_height_full(NOT_EXIST_INDEX).is_none());
+        assert!(INDEX_BIN.get_by_height_full(4308).is_none());
+        assert_eq!(INDEX_BIN.get_by_height_full(4307).unwrap(), *BLOCK_4307);
+        assert_eq!(INDEX_BIN.get_by_height_tx_root(4307), None);
+        assert_eq!(INDEX_BIN.get_by_height_indep_hash_orig(4307).unwrap(), BLOCK_4307_ORIG.hash);
+        assert_eq!(INDEX_BIN.get_by_height_weave_size_orig(4307).unwrap(), BLOCK_4307_ORIG.weave_size);
+        assert_eq!(INDEX_BIN.get_by_height_tx_root_orig(4307).unwrap(), BLOCK_4307_ORIG.tx_root);
+
+        for height in 0..4308 {
+            assert_eq!(INDEX_BIN.get_by_height_full(height), INDEX.get_by_height_full(height));
+            assert_eq!(INDEX_BIN.get_by_height_tx_root_orig(height), INDEX.get_by_height_tx_root_orig(height));
+        }
+    }
+
+    #[test]
+    fn bin_get_by_chunk_offset() {
+        assert!(INDEX_BIN.get_by_chunk_offset_full(NOT_EXIST_OFFSET).is_none());
+        assert!(INDEX_BIN.get_by_chunk_offset_full(1039029 + 1).is_none());
+        assert_eq!(INDEX_BIN.get_by_chunk_offset_full(1039029).unwrap(), *BLOCK_I780);
+        assert_eq!(INDEX_BIN.get_by_chunk_offset_indep_hash(1039029).unwrap(), BLOCK_I780.indep_hash);
+        assert_eq!(INDEX_BIN.get_by_chunk_offset_weave_size(1039029).unwrap(), BLOCK_I780.weave_size);
+        assert_eq!(INDEX_BIN.get_by_chunk_offset_tx_root(1039029), BLOCK_I780.tx_root);
+        assert_eq!(INDEX_BIN.get_by_chunk_offset_indep_hash_orig(1039029).unwrap(), BLOCK_I780_ORIG.hash);
+        assert_eq!(INDEX_BIN.get_by_chunk_offset_weave_size_orig(1039029).unwrap(), BLOCK_I780_ORIG.weave_size);
+        assert_eq!(INDEX_BIN.get_by_chunk_offset_tx_root_orig(1039029).unwrap(), BLOCK_I780_ORIG.tx_root);
+
+        for chunk_offset in [0, 1, 599058 - 1, 599058, 599058 + 1, 1039029] {
+            assert_eq!(INDEX_BIN.get_by_chunk_offset_full(chunk_offset), INDEX.get_by_chunk_offset_full(chunk_offset));
+        }
+    }
+
+    #[test]
+    fn decoded_save_sync() -> Result<(), Box<dyn std::error::Error>> {
+        let path = "../test_asset/block_index_slice";
+        // NOTE. unique name, see test_save_sync
+        let target_file = "../test_asset/block_index_slice_re4";
+        INDEX_DECODED.save_sync(target_file)?;
+
+        let mut buf1 = Vec::new();
+        let mut buf2 = Vec::new();
+        File::open(path)?.read_to_end(&mut buf1)?;
+        File::open(target_file)?.read_to_end(&mut buf2)?;
+        assert_eq!(buf1, buf2);
+
+        std::fs::remove_file(target_file)?;
+
+        let index = BlockIndex3Decoded::from_json(&INDEX)?;
+        assert_eq!(index.len(), INDEX_DECODED.len());
+        assert_eq!(index.to_json()?.block_list, INDEX.block_list);
+        Ok(())
+    }
+
+    #[test]
+    fn decoded_get_by_height() {
+        assert!(INDEX_DECODED.get_by_height_full(NOT_EXIST_INDEX).is_none());
+        assert!(INDEX_DECODED.get_by_height_full(4308).is_none());
+        assert_eq!(INDEX_DECODED.get_by_height_full(4307).unwrap(), *BLOCK_4307);
+        assert_eq!(INDEX_DECODED.get_by_height_indep_hash(4307).unwrap(), BLOCK_4307.indep_hash);
+        assert_eq!(INDEX_DECODED.get_by_height_weave_size(4307).unwrap(), BLOCK_4307.weave_size);
+        assert_eq!(INDEX_DECODED.get_by_height_tx_root(4307), None);
+        assert_eq!(INDEX_DECODED.get_by_height_indep_hash_orig(4307).unwrap(), BLOCK_4307_ORIG.hash);
+        assert_eq!(INDEX_DECODED.get_by_height_weave_size_orig(4307).unwrap(), BLOCK_4307_ORIG.weave_size);
+        assert_eq!(INDEX_DECODED.get_by_height_tx_root_orig(4307).unwrap(), BLOCK_4307_ORIG.tx_root);
+
+        for height in 0..4308 {
+            assert_eq!(INDEX_DECODED.get_by_height_full(height), INDEX.get_by_height_full(height));
+            assert_eq!(INDEX_DECODED.get_by_height_indep_hash_orig(height), INDEX.get_by_height_indep_hash_orig(height));
+        }
+    }
+
+    #[test]
+    fn decoded_get_by_chunk_offset() {
+        assert!(INDEX_DECODED.get_by_chunk_offset_full(NOT_EXIST_OFFSET).is_none());
+        assert!(INDEX_DECODED.get_by_chunk_offset_full(1039029 + 1).is_none());
+        assert_eq!(INDEX_DECODED.get_by_chunk_offset_full(1039029).unwrap(), *BLOCK_I780);
+        assert_eq!(INDEX_DECODED.get_by_chunk_offset_indep_hash(1039029).unwrap(), BLOCK_I780.indep_hash);
+        assert_eq!(INDEX_DECODED.get_by_chunk_offset_weave_size(1039029).unwrap(), BLOCK_I780.weave_size);
+        assert_eq!(INDEX_DECODED.get_by_chunk_offset_tx_root(1039029), BLOCK_I780.tx_root);
+        assert_eq!(INDEX_DECODED.get_by_chunk_offset_indep_hash_orig(1039029).unwrap(), BLOCK_I780_ORIG.hash);
+        assert_eq!(INDEX_DECODED.get_by_chunk_offset_weave_size_orig(1039029).unwrap(), BLOCK_I780_ORIG.weave_size);
+        assert_eq!(INDEX_DECODED.get_by_chunk_offset_tx_root_orig(1039029).unwrap(), BLOCK_I780_ORIG.tx_root);
+
+        for chunk_offset in [0, 1, 599058 - 1, 599058, 599058 + 1, 1039029] {
+            assert_eq!(INDEX_DECODED.get_by_chunk_offset_full(chunk_offset), INDEX.get_by_chunk_offset_full(chunk_offset));
+        }
+    }
+
+    #[test]
+    fn sqlite_json_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("block_index_slice_sqlite");
+        let _ = std::fs::remove_file(&path);
+        let index = BlockIndex3Sqlite::from_json_sync(&INDEX, path.to_str().unwrap())?;
+        assert_eq!(index.len()?, 4308);
+        assert!(!index.is_empty()?);
+        assert_eq!(index.to_json()?.block_list, INDEX.block_list);
+
+        // other connection sees same data, reimport replaces
+        let mut index2 = BlockIndex3Sqlite::open(path.to_str().unwrap())?;
+        assert_eq!(index2.get_by_chunk_offset_full(1039029).unwrap(), *BLOCK_I780);
+        index2.import_json(&index_without_tip(780))?;
+        assert_eq!(index.get_tip_height(), Some(3527));
+        assert!(index.get_by_height_full(3528).is_none());
+
+        // broken db is error, not empty index
+        rusqlite::Connection::open(&path)?.execute("DROP TABLE block_index3", [])?;
+        assert!(matches!(index.len(), Err(BlockIndexError::Sqlite(_))));
+        assert!(index.is_empty().is_err());
+
+        drop(index);
+        drop(index2);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_get_by_height() {
+        let index = index_sqlite();
+        assert!(index.get_by_height_full(NOT_EXIST_INDEX).is_none());
+        assert!(index.get_by_height_full(4308).is_none());
+        assert_eq!(index.get_tip_height(), Some(4307));
+        assert_eq!(index.get_by_height_full(4307).unwrap(), *BLOCK_4307);
+        assert_eq!(index.get_by_height_indep_hash(4307).unwrap(), BLOCK_4307.indep_hash);
+        assert_eq!(index.get_by_height_weave_size(4307).unwrap(), BLOCK_4307.weave_size);
+        assert_eq!(index.get_by_height_tx_root(4307), None);
+        assert_eq!(index.get_by_height_indep_hash_orig(4307).unwrap(), BLOCK_4307_ORIG.hash);
+        assert_eq!(index.get_by_height_weave_size_orig(4307).unwrap(), BLOCK_4307_ORIG.weave_size);
+        assert_eq!(index.get_by_height_tx_root_orig(4307).unwrap(), BLOCK_4307_ORIG.tx_root);
+        assert_eq!(index.get_by_height_full(3527).unwrap(), *BLOCK_I780);
+
+        for height in 0..4308 {
+            assert_eq!(index.get_by_height_full(height), INDEX.get_by_height_full(height));
+            assert_eq!(index.get_by_height_tx_root_orig(height), INDEX.get_by_height_tx_root_orig(height));
+        }
+        assert!(BlockIndex3Sqlite::open_in_memory().unwrap().get_tip_height().is_none());
+    }
+
+    #[test]
+    fn sqlite_get_by_chunk_offset() {
+        let index = index_sqlite();
+        assert!(index.get_by_chunk_offset_full(NOT_EXIST_OFFSET).is_none());
+        assert!(index.get_by_chunk_offset_full(1039029 + 1).is_none());
+        assert_eq!(index.get_by_chunk_offset_full(1039029).unwrap(), *BLOCK_I780);
+        assert_eq!(index.get_by_chunk_offset_indep_hash(1039029).unwrap(), BLOCK_I780.indep_hash);
+        assert_eq!(index.get_by_chunk_offset_weave_size(1039029).unwrap(), BLOCK_I780.weave_size);
+        assert_eq!(index.get_by_chunk_offset_tx_root(1039029), BLOCK_I780.tx_root);
+        assert_eq!(index.get_by_chunk_offset_indep_hash_orig(1039029).unwrap(), BLOCK_I780_ORIG.hash);
+        assert_eq!(index.get_by_chunk_offset_weave_size_orig(1039029).unwrap(), BLOCK_I780_ORIG.weave_size);
+        assert_eq!(index.get_by_chunk_offset_tx_root_orig(1039029).unwrap(), BLOCK_I780_ORIG.tx_root);
+
+        for chunk_offset in [0, 1, 599058 - 1, 599058, 599058 + 1, 1039029] {
+            assert_eq!(index.get_by_chunk_offset_full(chunk_offset), INDEX.get_by_chunk_offset_full(chunk_offset));
+        }
+    }
+
+    // INDEX up to height 3526, then 2 blocks of other branch
+    fn forked_block_list() -> Vec<BlockIndex3JsonEntity> {
+        let mut block_list = vec![
+            BlockIndex3JsonEntity {
+                tx_root: "".into(),
+                weave_size: "700000".into(),
+                hash: INDEX.block_list[4000].hash.clone(),
+            },
+            BlockIndex3JsonEntity {
+                tx_root: BLOCK_0_ORIG.tx_root.clone(),
+                weave_size: "700000".into(),
+                hash: INDEX.block_list[4001].hash.clone(),
+            },
+        ];
+        block_list.extend_from_slice(&INDEX.block_list[781..]);
+        block_list
+    }
+
+    #[test]
+    fn test_find_fork_height() {
+        let mut forked = BlockIndex3Json::new();
+        forked._load_from_original_format(forked_block_list()).unwrap();
+        assert_eq!(find_fork_height(&*INDEX, &*INDEX), Some(4307));
+        assert_eq!(find_fork_height(&*INDEX, &*INDEX_DECODED), Some(4307));
+        assert_eq!(find_fork_height(&*INDEX, &forked), Some(3526));
+        assert_eq!(find_fork_height(&index_without_tip(8), &*INDEX_BIN), Some(4299));
+        assert_eq!(find_fork_height(&*INDEX, &BlockIndex3Json::new()), None);
+    }
+
+    #[test]
+    fn test_reorg() -> Result<(), Box<dyn std::error::Error>> {
+        let mut forked = BlockIndex3Json::new();
+        forked._load_from_original_format(forked_block_list())?;
+        let expected = ReorgRes {
+            fork_height: 3526,
+            orphaned_list: vec![OrphanedBlock {
+                height: 3527,
+                indep_hash: BLOCK_I780.indep_hash,
+                tx_root: BLOCK_I780.tx_root,
+                weave_start: 599058,
+                weave_end: 1039029,
+            }],
+            invalidated_weave_start: 599058,
+            invalidated_weave_end: 1039029,
+            applied_count: 2,
+        };
+
+        let assert_reverse_reorged = |index: &dyn BlockIndex3Reverse| {
+            assert_eq!(index.get_height_by_indep_hash(&BLOCK_I780.indep_hash), None);
+            assert!(index.get_heights_by_tx_root(&BLOCK_I780.tx_root.unwrap()).is_empty());
+            let tip_hash = index.get_by_height_indep_hash(3528).unwrap();
+            assert_eq!(index.get_height_by_indep_hash(&tip_hash), Some(3528));
+        };
+
+        let mut index = index_without_tip(780);
+        assert_eq!(index.reorg(&forked)?, expected);
+        assert_eq!(index.block_list, forked.block_list);
+        assert_eq!(index.get_by_chunk_offset_weave_size(600000), Some(700000));
+        assert!(index.get_by_chunk_offset_full(1039029).is_none());
+        assert_reverse_reorged(&index);
+
+        let mut index = BlockIndex3Decoded::from_json(&index_without_tip(780))?;
+        assert_eq!(index.reorg(&forked)?, expected);
+        assert_eq!(index.to_json()?.block_list, forked.block_list);
+        assert_reverse_reorged(&index);
+
+        // other is behind, nothing to apply
+        let mut index = index_without_tip(780);
+        let res = index.reorg(&index_without_tip(790))?;
+        assert_eq!(res.fork_height, 3517);
+        assert!(res.orphaned_list.is_empty());
+        assert_eq!(res.applied_count, 0);
+        assert_eq!(res.invalidated_weave_start, res.invalidated_weave_end);
+        assert_eq!(index.block_list, INDEX.block_list[780..]);
+
+        // other only extends
+        let res = index.reorg(&*INDEX)?;
+        assert_eq!(res.fork_height, 3527);
+        assert!(res.orphaned_list.is_empty());
+        assert_eq!(res.applied_count, 780);
+        assert_eq!(index.block_list, INDEX.block_list);
+        assert_eq!(index.get_height_by_indep_hash(&BLOCK_4307.indep_hash), Some(4307));
+
+        // broken other branch, local index is untouched
+        let mut broken_block_list = forked_block_list();
+        broken_block_list[0].weave_size = "1".into();
+        let mut broken = BlockIndex3Json::new();
+        broken.block_list = broken_block_list;
+        let mut index = index_without_tip(780);
+        assert!(matches!(index.reorg(&broken), Err(BlockIndexError::NonMonotonicWeaveSize { .. })));
+        assert_eq!(index.block_list, INDEX.block_list[780..]);
+        assert_eq!(index.get_height_by_indep_hash(&BLOCK_I780.indep_hash), Some(3527));
+        assert_eq!(index.get_heights_by_tx_root(&BLOCK_I780.tx_root.unwrap()), vec![3527]);
+        Ok(())
+    }
+
+    #[test]
+    fn journal_replay() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("block_index_slice_journal");
+        let journal_path = std::env::temp_dir().join("block_index_slice_journal.journal");
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&journal_path);
+        let path = path.to_str().unwrap();
+        let header_len = BLOCK_INDEX3_JOURNAL_HEADER_SIZE as u64;
+        let journal_len = || std::fs::metadata(&journal_path).unwrap().len();
+
+        let (mut journal, index) = BlockIndex3Journal::open(path)?;
+        assert!(index.block_list.is_empty());
+        assert_eq!(journal_len(), header_len);
+
+        let index = index_without_tip(8);
+        journal.append(&index, 0)?;
+        let (mut journal, reopened) = BlockIndex3Journal::open(path)?;
+        assert_eq!(reopened.block_list, index.block_list);
+        assert_eq!(journal.record_count(), 4300);
+
+        // sync appends only new top
+        journal.append(&INDEX, 4300)?;
+        drop(journal);
+        let (_, reopened) = BlockIndex3Journal::open(path)?;
+        assert_eq!(reopened.block_list, INDEX.block_list);
+        assert_eq!(reopened.get_height_by_indep_hash(&BLOCK_4307.indep_hash), Some(4307));
+
+        // crash in the middle of write
+        let mut file = std::fs::OpenOptions::new().append(true).open(&journal_path)?;
+        file.write_all(&[0xAB; 50])?;
+        drop(file);
+        let (journal, reopened) = BlockIndex3Journal::open(path)?;
+        assert_eq!(journal.torn_tail_len(), 50);
+        assert_eq!(reopened.block_list, INDEX.block_list);
+        assert_eq!(journal_len(), header_len + 4308 * BLOCK_INDEX3_JOURNAL_RECORD_SIZE as u64);
+        drop(journal);
+
+        // last record is damaged
+        let mut buf = std::fs::read(&journal_path)?;
+        let len = buf.len();
+        buf[len - 10] ^= 1;
+        std::fs::write(&journal_path, &buf)?;
+        let (mut journal, reopened) = BlockIndex3Journal::open(path)?;
+        assert_eq!(journal.torn_tail_len(), BLOCK_INDEX3_JOURNAL_RECORD_SIZE as u64);
+        assert_eq!(reopened.get_tip_height(), Some(4306));
+        assert_eq!(reopened.block_list, INDEX.block_list[1..]);
+
+        journal.compact(&INDEX)?;
+        assert_eq!(journal.record_count(), 0);
+        assert_eq!(journal_len(), header_len);
+        let (_, reopened) = BlockIndex3Journal::open(path)?;
+        assert_eq!(reopened.block_list, INDEX.block_list);
+        // snapshot is plain original format
+        let mut loaded = BlockIndex3Json::new();
+        loaded.load_sync(path)?;
+        assert_eq!(loaded.block_list, INDEX.block_list);
+
+        std::fs::remove_file(path)?;
+        std::fs::remove_file(&journal_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn journal_reorg_compact() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("block_index_slice_journal_reorg");
+        let journal_path = std::env::temp_dir().join("block_index_slice_journal_reorg.journal");
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&journal_path);
+        let path = path.to_str().unwrap();
+
+        let (mut journal, _) = BlockIndex3Journal::open(path)?;
+        let mut index = index_without_tip(780);
+        journal.append(&index, 0)?;
+        journal.compact(&index)?;
+
+        let mut forked = BlockIndex3Json::new();
+        forked._load_from_original_format(forked_block_list())?;
+        let res = index.reorg(&forked)?;
+        journal.append(&index, res.fork_height + 1)?;
+        assert_eq!(journal.record_count(), 2);
+        let stale_journal = std::fs::read(&journal_path)?;
+        let (_, reopened) = BlockIndex3Journal::open(path)?;
+        assert_eq!(reopened.block_list, forked.block_list);
+
+        // crash after new snapshot rename, before journal reset
+        journal.compact(&index)?;
+        std::fs::write(&journal_path, &stale_journal)?;
+        let (journal, reopened) = BlockIndex3Journal::open(path)?;
+        assert_eq!(reopened.block_list, forked.block_list);
+        assert_eq!(journal.record_count(), 0);
+        assert_eq!(std::fs::metadata(&journal_path)?.len(), BLOCK_INDEX3_JOURNAL_HEADER_SIZE as u64);
+        drop(journal);
+
+        // append compacts by threshold
+        let (mut journal, _) = BlockIndex3Journal::open(path)?;
+        journal.compact_threshold = 5;
+        let mut index = index_without_tip(784);
+        journal.append(&index, 0)?;
+        assert_eq!(journal.record_count(), 0);
+        let res = index.reorg(&index_without_tip(780))?;
+        journal.append(&index, res.fork_height + 1)?;
+        assert_eq!(journal.record_count(), 4);
+        let (_, reopened) = BlockIndex3Journal::open(path)?;
+        assert_eq!(reopened.block_list, INDEX.block_list[780..]);
+
+        // not a journal
+        std::fs::write(&journal_path, b"garbage")?;
+        assert!(matches!(BlockIndex3Journal::open(path), Err(BlockIndexError::Corrupt(_))));
+
+        std::fs::remove_file(path)?;
+        std::fs::remove_file(&journal_path)?;
+        Ok(())
+    }
+
+    // heights and hashes of test slice itself, only checks code, proves nothing about mainnet
+    fn checkpoint_list_slice() -> CheckpointList {
+        CheckpointList::load_sync("../test_asset/checkpoints_slice.json").unwrap()
+    }
+
+    #[test]
+    fn checkpoint_verify() -> Result<(), Box<dyn std::error::Error>> {
+        let checkpoint_list = checkpoint_list_slice();
+        assert_eq!(checkpoint_list.len(), 8);
+        assert_eq!(verify_against_checkpoints(&*INDEX, &checkpoint_list)?, 8);
+        assert_eq!(verify_against_checkpoints(&*INDEX_BIN, &checkpoint_list)?, 8);
+        assert_eq!(verify_against_checkpoints(&*INDEX_DECODED, &checkpoint_list)?, 8);
+        assert_eq!(verify_against_checkpoints(&index_sqlite(), &checkpoint_list)?, 8);
+        // checkpoints above tip are skipped
+        assert_eq!(verify_against_checkpoints(&index_without_tip(780), &checkpoint_list)?, 6);
+        assert_eq!(verify_against_checkpoints(&BlockIndex3Json::new(), &checkpoint_list)?, 0);
+
+        let mut forked = BlockIndex3Json::new();
+        forked._load_from_original_format(forked_block_list())?;
+        let err = verify_against_checkpoints(&forked, &checkpoint_list).unwrap_err();
+        assert!(matches!(err, BlockIndexError::CheckpointMismatch { height: 3527, field: "indep_hash" }), "{}", err);
+        assert_eq!(err.to_string(), "block at height 3527 contradicts checkpoint indep_hash");
+
+        // file
+        let path = std::env::temp_dir().join("block_index_checkpoints");
+        let checkpoint = |weave_size: &str| format!("{{\"height\": 82, \"indep_hash\": \"{}\", \"weave_size\": \"{}\"}}", INDEX.block_list[4308 - 1 - 82].hash, weave_size);
+        fs::write(&path, format!("[{}, {}]", checkpoint("599058"), checkpoint("599058")))?;
+        let checkpoint_list = CheckpointList::load_sync(path.to_str().unwrap())?;
+        assert_eq!(checkpoint_list.list(), &[Checkpoint { height: 82, indep_hash: INDEX.get_by_height_indep_hash(82).unwrap(), weave_size: 599058 }]);
+        fs::write(&path, format!("[{}]", checkpoint("599059")))?;
+        let checkpoint_list = CheckpointList::load_sync(path.to_str().unwrap())?;
+        let err = verify_against_checkpoints(&*INDEX, &checkpoint_list).unwrap_err();
+        assert!(matches!(err, BlockIndexError::CheckpointMismatch { height: 82, field: "weave_size" }), "{}", err);
+        fs::remove_file(&path)?;
+
+        assert!(matches!(CheckpointList::from_json_str(&format!("[{}, {}]", checkpoint("1"), checkpoint("2"))), Err(BlockIndexError::InvalidArgument(_))));
+        assert!(matches!(CheckpointList::from_json_str(&format!("[{}]", checkpoint("+1"))), Err(BlockIndexError::BadWeaveSize { idx: 0 })));
+        let bad_hash = checkpoint("1").replace(&INDEX.block_list[4308 - 1 - 82].hash, "abc");
+        assert!(matches!(CheckpointList::from_json_str(&format!("[{}]", bad_hash)), Err(BlockIndexError::BadBase64 { idx: 0, field: "indep_hash", .. })));
+        assert!(matches!(CheckpointList::from_json_str("{}"), Err(BlockIndexError::Json(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn checkpoint_reject() -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = BlockIndex3Json::new();
+        index.set_checkpoint_list(Some(checkpoint_list_slice()));
+        index.load_sync("../test_asset/block_index_slice")?;
+        assert_eq!(index.block_list, INDEX.block_list);
+
+        // rejected load keeps current index
+        let forked_json = serde_json::to_string(&forked_block_list())?;
+        let err = index.load_from_reader(forked_json.as_bytes()).unwrap_err();
+        assert!(matches!(err, BlockIndexError::CheckpointMismatch { height: 3527, .. }), "{}", err);
+        assert_eq!(index.block_list, INDEX.block_list);
+
+        // reorg onto contradicting branch is rolled back
+        let mut forked = BlockIndex3Json::new();
+        forked._load_from_original_format(forked_block_list())?;
+        let mut index = index_without_tip(780);
+        index.set_checkpoint_list(Some(checkpoint_list_slice()));
+        assert!(matches!(index.reorg(&forked), Err(BlockIndexError::CheckpointMismatch { height: 3527, .. })));
+        assert_eq!(index.block_list, INDEX.block_list[780..]);
+        assert_eq!(index.reorg(&*INDEX)?.applied_count, 780);
+
+        // download skips peer on other chain
+        let peer_forked_url = format!("http://{}", spawn_test_server(peer_forked_handler));
+        let peer_full_url = format!("http://{}", spawn_test_server(peer_full_handler));
+        let rt = Runtime::new()?;
+        let mut index = BlockIndex3Json::new();
+        index.set_checkpoint_list(Some(checkpoint_list_slice()));
+        let err = rt.block_on(index.download(std::slice::from_ref(&peer_forked_url))).unwrap_err();
+        // not retried, peer will not change its chain
+        match &err {
+            BlockIndexError::AllPeersFailed(err) => {
+                assert_eq!(err.peer_res_list.len(), 1);
+                assert_eq!(err.peer_res_list[0].err_list.len(), 1);
+                assert!(err.peer_res_list[0].err_list[0].starts_with("block at height 4307 contradicts checkpoint"));
+            }
+            _ => panic!("{}", err),
+        }
+        assert!(index.block_list.is_empty());
+        rt.block_on(index.download(&[peer_forked_url.clone(), peer_full_url]))?;
+        assert_eq!(index.block_list, INDEX.block_list);
+
+        // without checkpoints anything goes
+        let mut index = BlockIndex3Json::new();
+        rt.block_on(index.download(&[peer_forked_url]))?;
+        assert_eq!(index.get_tip_height(), Some(4307));
+        Ok(())
+    }
+
+    #[test]
+    fn diff_block_index() -> Result<(), Box<dyn std::error::Error>> {
+        let res = diff(&*INDEX, &*INDEX_BIN);
+        assert!(res.is_same());
+        assert_eq!(res.a_tip_height, Some(4307));
+        assert!(diff(&*INDEX_DECODED, &index_sqlite()).is_same());
+
+        // prefix
+        let res = diff(&*INDEX, &index_without_tip(780));
+        assert!(!res.is_same());
+        assert_eq!(res.b_tip_height, Some(3527));
+        assert_eq!(res.first_diverging_height, None);
+        assert!(res.affected_range_list.is_empty());
+        assert!(!diff(&*INDEX, &BlockIndex3Json::new()).is_same());
+
+        let mut forked = BlockIndex3Json::new();
+        forked._load_from_original_format(forked_block_list())?;
+        let res = diff(&*INDEX, &forked);
+        assert_eq!(res.first_diverging_height, Some(3527));
+        assert!(res.tx_root_only_list.is_empty());
+        assert!(res.weave_size_only_list.is_empty());
+        assert_eq!(res.affected_range_list, vec![WeaveRange { weave_start: 599058, weave_end: 1039029 }]);
+        assert_eq!(res.affected_weave_size, 439971);
+
+        // same indep_hash, broken fields
+        let mut block_list = INDEX.block_list.clone();
+        block_list[4308 - 1 - 82].tx_root = BLOCK_0_ORIG.tx_root.clone();
+        block_list[4308 - 1 - 3526].weave_size = "600000".into();
+        let mut broken = BlockIndex3Json::new();
+        broken._load_from_original_format(block_list)?;
+        let res = diff(&*INDEX_BIN, &broken);
+        assert_eq!(res.first_diverging_height, None);
+        assert_eq!(res.tx_root_only_list, vec![82]);
+        assert_eq!(res.weave_size_only_list, vec![3526]);
+        // 3527 differs in block_size only, ranges touch and are merged
+        assert_eq!(res.affected_range_list, vec![WeaveRange { weave_start: 0, weave_end: 1039029 }]);
+        assert_eq!(serde_json::to_value(&res)?["affected_range_list"][0]["weave_end"], 1039029);
+        Ok(())
+    }
+
+    #[test]
+    fn server_routes() -> Result<(), Box<dyn std::error::Error>> {
+        assert!(matches!(BlockIndexServer::new(BlockIndex3Json::new()), Err(BlockIndexError::NotLoaded)));
+        let server = std::sync::Arc::new(BlockIndexServer::new(index_without_tip(0))?);
+        let rt = Runtime::new()?;
+        let (addr, fut) = rt.block_on(async { server.serve(([127, 0, 0, 1], 0).into()) })?;
+        rt.spawn(fut);
+        let url = format!("http://{}", addr);
+
+        let get = |path: &str| -> (u16, serde_json::Value) {
+            rt.block_on(async {
+                let response = reqwest::get(format!("{}{}", url, path)).await.unwrap();
+                (response.status().as_u16(), serde_json::from_slice(&response.bytes().await.unwrap()).unwrap())
+            })
+        };
+        let block_3527 = serde_json::json!({
+            "height": 3527,
+            "indep_hash": BLOCK_I780_ORIG.hash,
+            "weave_size": "1039029",
+            "tx_root": BLOCK_I780_ORIG.tx_root,
+        });
+        assert_eq!(get("/height/3527"), (200, block_3527.clone()));
+        assert_eq!(get(&format!("/hash/{}", BLOCK_I780_ORIG.hash)), (200, block_3527.clone()));
+        assert_eq!(get("/height/4307").1["tx_root"], "");
+
+        let (status, block) = get("/offset/599059");
+        assert_eq!(status, 200);
+        assert_eq!(block["height"], 3527);
+        assert_eq!(block["block_start"], "599058");
+        assert_eq!(block["block_end"], "1039029");
+        assert_eq!(block["block_size"], "439971");
+
+        assert_eq!(get("/height/4308").0, 404);
+        assert_eq!(get("/height/18446744073709551615").0, 404);
+        assert_eq!(get("/height/x").0, 400);
+        assert_eq!(get("/offset/1039030").0, 404);
+        assert_eq!(get("/hash/abc").0, 400);
+        assert_eq!(get(&format!("/hash/{}", BLOCK_I780_ORIG.hash.replace('2', "3"))).0, 404);
+        assert_eq!(get("/block_index/10/5").0, 400);
+        assert_eq!(get("/block_index/4300/4308").0, 404);
+        let (status, err) = get("/nope");
+        assert_eq!(status, 404);
+        assert_eq!(err["error"], "not found");
+
+        // works as peer for download and sync
+        let mut index = BlockIndex3Json::new();
+        rt.block_on(index.download(std::slice::from_ref(&url)))?;
+        assert_eq!(index.block_list, INDEX.block_list);
+        let mut index = index_without_tip(2500);
+        assert_eq!(rt.block_on(index.sync(&[url]))?, 2500);
+        assert_eq!(index.block_list, INDEX.block_list);
+        Ok(())
+    }
+
+    // /info answers height (500 if None), /peers answers peer_list, rest as peer_full_handler unless broken
+    fn spawn_peer_stand_in(height: Option<HeightType>, peer_list: Vec<String>, broken: bool) -> String {
+        let addr = spawn_test_server(move |req| {
+            let status = |status: u16| hyper::Response::builder().status(status).body(hyper::Body::empty()).unwrap();
+            match (req.uri().path(), height) {
+                ("/info", Some(height)) => hyper::Response::new(hyper::Body::from(format!("{{\"height\":{}}}", height))),
+                ("/info", None) => status(500),
+                ("/peers", _) => hyper::Response::new(hyper::Body::from(serde_json::to_string(&peer_list).unwrap())),
+                _ if broken => status(500),
+                _ => peer_full_handler(req),
+            }
+        });
+        addr.to_string()
+    }
+
+    #[test]
+    fn peer_manager() -> Result<(), Box<dyn std::error::Error>> {
+        let c = spawn_peer_stand_in(None, Vec::new(), true);
+        let b = spawn_peer_stand_in(Some(4300), vec![c.clone()], false);
+        // arweave lists peers without scheme
+        let dead = dead_peer_url().trim_start_matches("http://").to_string();
+        let a = spawn_peer_stand_in(Some(4307), vec![b.clone(), "bad peer!".into(), dead.clone(), format!("http://{}/", b)], true);
+        let url = |addr: &str| format!("http://{}", addr);
+        // stand-ins are on loopback
+        let loopback_config = || PeerManagerConfig { reject_private_peers: false, ..PeerManagerConfig::default() };
+
+        assert!(matches!(PeerManager::new(&[], PeerManagerConfig::default()), Err(BlockIndexError::NoPeer)));
+        assert!(matches!(PeerManager::new(&["bad peer!".into()], PeerManagerConfig::default()), Err(BlockIndexError::InvalidArgument(_))));
+
+        let rt = Runtime::new()?;
+        let mut peer_manager = PeerManager::new(std::slice::from_ref(&a), loopback_config())?;
+        assert_eq!(rt.block_on(peer_manager.discover()), 3);
+        assert_eq!(peer_manager.len(), 4);
+        // dead peer failed on /peers
+        assert_eq!(peer_manager.get(&url(&dead)).unwrap().failure_count, 1);
+
+        rt.block_on(peer_manager.probe());
+        assert_eq!(peer_manager.best_height(), Some(4307));
+        let stat_a = peer_manager.get(&url(&a)).unwrap();
+        assert_eq!(stat_a.height, Some(4307));
+        assert!(stat_a.latency.is_some());
+        assert_eq!(peer_manager.get(&url(&c)).unwrap().height, None);
+        // fresh, lagging by 7, then never answered by reliability
+        assert_eq!(peer_manager.ranked_peer_url_list(), vec![url(&a), url(&b), url(&c), url(&dead)]);
+
+        // a has no /block_index, falls through to b
+        let mut index = BlockIndex3Json::new();
+        let res = rt.block_on(index.download_ranked(&mut peer_manager, &fast_download_config()))?;
+        assert_eq!(res.peer_url, url(&b));
+        assert_eq!(res.peer_res_list[0].err_list.len(), 3);
+        assert_eq!(index.block_list, INDEX.block_list);
+        let stat_a = peer_manager.get(&url(&a)).unwrap();
+        assert_eq!((stat_a.success_count, stat_a.failure_count), (1, 1));
+
+        // max_peer_count caps discovery
+        let config = PeerManagerConfig { max_peer_count: 2, ..loopback_config() };
+        let mut peer_manager = PeerManager::new(std::slice::from_ref(&a), config)?;
+        rt.block_on(peer_manager.refresh());
+        assert_eq!(peer_manager.len(), 2);
+
+        // by default only public ip from /peers, loopback seed is kept
+        let mut peer_manager = PeerManager::new(std::slice::from_ref(&a), PeerManagerConfig::default())?;
+        assert_eq!(rt.block_on(peer_manager.discover()), 0);
+        assert_eq!(peer_manager.len(), 1);
+        let private_list = ["169.254.169.254:80", "10.1.2.3:1984", "192.168.0.1:1984", "100.64.0.1:1984", "0.0.0.0:1984",
+            "[::1]:1984", "[fe80::1]:1984", "[fd00::1]:1984", "[::ffff:127.0.0.1]:1984", "localhost:1984", "http://metadata.internal"];
+        let public_list = ["1.1.1.1:1984", "[2606:4700::1111]:1984"];
+        let e = spawn_peer_stand_in(Some(4307), private_list.iter().chain(public_list.iter()).map(|v| v.to_string()).collect(), true);
+        // depth 1, public peers are only listed, never asked
+        let config = PeerManagerConfig { discover_depth: 1, ..PeerManagerConfig::default() };
+        let mut peer_manager = PeerManager::new(&[e], config)?;
+        assert_eq!(rt.block_on(peer_manager.discover()), 2);
+        assert!(peer_manager.get("http://1.1.1.1:1984").is_some());
+        assert!(peer_manager.get("http://[2606:4700::1111]:1984").is_some());
+        Ok(())
+    }
+
+    // INDEX with 2 top blocks replaced
+    fn peer_forked_handler(_req: hyper::Request<hyper::Body>) -> hyper::Response<hyper::Body> {
+        let mut block_list = INDEX.block_list.clone();
+        block_list[0].hash = INDEX.block_list[4000].hash.clone();
+        block_list[1].hash = INDEX.block_list[4001].hash.clone();
+        hyper::Response::new(hyper::Body::from(serde_json::to_string(&block_list).unwrap()))
+    }
+
+    // claims to be ahead, serves its fork which is behind INDEX tip
+    fn peer_forked_behind_handler(req: hyper::Request<hyper::Body>) -> hyper::Response<hyper::Body> {
+        let mut block_list = INDEX.block_list[12..].to_vec();
+        block_list[0].hash = INDEX.block_list[4000].hash.clone();
+        block_list[1].hash = INDEX.block_list[4001].hash.clone();
+        let path: Vec<&str> = req.uri().path().split('/').filter(|v| !v.is_empty()).collect();
+        let body = match path.as_slice() {
+            ["info"] => format!("{{\"height\":{}}}", INDEX.block_list.len() - 1),
+            ["block_index"] => serde_json::to_string(&block_list).unwrap(),
+            ["block_index", from, to] => {
+                let count = to.parse::<usize>().unwrap() - from.parse::<usize>().unwrap() + 1;
+                serde_json::to_string(&vec![block_list[0].clone(); count]).unwrap()
+            }
+            _ => return hyper::Response::builder().status(404).body(hyper::Body::empty()).unwrap(),
+        };
+        hyper::Response::new(hyper::Body::from(body))
+    }
+
+    fn peer_lagging_handler(_req: hyper::Request<hyper::Body>) -> hyper::Response<hyper::Body> {
+        hyper::Response::new(hyper::Body::from(serde_json::to_string(&INDEX.block_list[8..]).unwrap()))
+    }
+
+    #[test]
+    fn download_retry() -> Result<(), Box<dyn std::error::Error>> {
+        let status = |code: u16| hyper::Response::builder().status(code).body(hyper::Body::empty()).unwrap();
+        let garbage_url = format!("http://{}", spawn_test_server(|_| hyper::Response::new(hyper::Body::from("not json"))));
+        let missing_url = format!("http://{}", spawn_test_server(move |_| status(404)));
+        let broken_url = format!("http://{}", spawn_test_server(move |_| status(500)));
+        // 500 on first request, then serves INDEX
+        let request_count = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let flaky_request_count = request_count.clone();
+        let flaky_url = format!("http://{}", spawn_test_server(move |req| {
+            match flaky_request_count.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 => status(500),
+                _ => peer_full_handler(req),
+            }
+        }));
+        let dead_url = dead_peer_url();
+        let config = fast_download_config();
+        let rt = Runtime::new()?;
+
+        // garbage is not retried, 500 is retried on same peer
+        let mut index = BlockIndex3Json::new();
+        let res = rt.block_on(index.download_with_config(&[garbage_url.clone(), flaky_url.clone()], &config))?;
+        assert_eq!(res.peer_url, flaky_url);
+        assert_eq!(res.peer_res_list.len(), 2);
+        assert_eq!((res.peer_res_list[0].ok, res.peer_res_list[0].err_list.len()), (false, 1));
+        assert_eq!((res.peer_res_list[1].ok, res.peer_res_list[1].err_list.len()), (true, 1));
+        assert_eq!(res.peer_res_list[1].err_list[0], format!("{}/block_index: status 500", flaky_url));
+        assert_eq!(request_count.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(index.block_list, INDEX.block_list);
+
+        // every peer with every attempt is reported, index is untouched
+        let peer_url_list = vec![garbage_url, missing_url.clone(), broken_url, dead_url];
+        let err = rt.block_on(index.download_with_config(&peer_url_list, &config)).unwrap_err();
+        let err = match err {
+            BlockIndexError::AllPeersFailed(err) => err,
+            err => panic!("{}", err),
+        };
+        assert_eq!(err.peer_res_list.iter().map(|v| v.peer_url.clone()).collect::<Vec<_>>(), peer_url_list);
+        assert_eq!(err.peer_res_list.iter().map(|v| v.err_list.len()).collect::<Vec<_>>(), vec![1, 1, 3, 3]);
+        assert!(err.peer_res_list.iter().all(|v| !v.ok));
+        assert_eq!(err.peer_res_list[1].err_list[0], format!("{}/block_index: status 404", missing_url));
+        assert_eq!(index.block_list, INDEX.block_list);
+
+        let config = DownloadConfig { retry_count: 0, ..config };
+        let err = rt.block_on(index.download_with_config(&peer_url_list[2..], &config)).unwrap_err();
+        assert!(matches!(&err, BlockIndexError::AllPeersFailed(err) if err.peer_res_list.iter().all(|v| v.err_list.len() == 1)));
+        assert!(matches!(rt.block_on(index.download(&[])), Err(BlockIndexError::NoPeer)));
+
+        let config = DownloadConfig::default();
+        assert_eq!(config.backoff(0), std::time::Duration::from_millis(500));
+        assert_eq!(config.backoff(1), std::time::Duration::from_secs(1));
+        assert_eq!(config.backoff(10), std::time::Duration::from_secs(10));
+        assert_eq!(config.backoff(100), std::time::Duration::from_secs(10));
+        Ok(())
+    }
+
+    #[test]
+    fn download_progress_cancel() -> Result<(), Box<dyn std::error::Error>> {
+        let body = serde_json::to_vec(&INDEX.block_list)?;
+        let body_len = body.len();
+        let peer_full_url = format!("http://{}", spawn_test_server(peer_full_handler));
+        // sends first half of /block_index and stalls
+        let stalled_url = format!("http://{}", spawn_test_server(move |_| {
+            let (mut tx, stalled_body) = hyper::Body::channel();
+            let part = hyper::body::Bytes::copy_from_slice(&body[..body.len() / 2]);
+            tokio::spawn(async move {
+                tx.send_data(part).await.unwrap();
+                futures::future::pending::<()>().await;
+                drop(tx);
+            });
+            hyper::Response::new(stalled_body)
+        }));
+        let rt = Runtime::new()?;
+        let config = fast_download_config();
+
+        let mut progress_list: Vec<DownloadProgress> = Vec::new();
+        let mut index = BlockIndex3Json::new();
+        let res = rt.block_on(index.download_with_progress(
+            std::slice::from_ref(&peer_full_url),
+            &config,
+            &mut |progress| progress_list.push(progress.clone()),
+            &CancellationToken::new(),
+        ))?;
+        assert_eq!(res.peer_url, peer_full_url);
+        assert_eq!(progress_list[0], DownloadProgress { peer_url: peer_full_url.clone(), attempt: 0, byte_count: 0, entry_count: 0 });
+        let last = progress_list.last().unwrap();
+        assert_eq!((last.byte_count, last.entry_count), (body_len as u64, 4308));
+        assert!(progress_list.windows(2).all(|v| v[0].byte_count <= v[1].byte_count && v[0].entry_count <= v[1].entry_count));
+
+        // cancelled mid-body, old index stays
+        let mut index = index_without_tip(10);
+        let cancel = CancellationToken::new();
+        let err = rt.block_on(index.download_with_progress(
+            &[stalled_url, peer_full_url],
+            &config,
+            &mut |progress| if progress.entry_count > 0 { cancel.cancel() },
+            &cancel,
+        )).unwrap_err();
+        assert!(matches!(err, BlockIndexError::Cancelled), "{}", err);
+        assert_eq!(index.block_list, INDEX.block_list[10..]);
+
+        // cancelled during backoff, backoff_max caps delay so both are set far above cancel time
+        let request_count = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let broken_request_count = request_count.clone();
+        let broken_url = format!("http://{}", spawn_test_server(move |_| {
+            broken_request_count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            hyper::Response::builder().status(500).body(hyper::Body::empty()).unwrap()
+        }));
+        let config = DownloadConfig {
+            backoff_base: std::time::Duration::from_secs(3600),
+            backoff_max: std::time::Duration::from_secs(3600),
+            ..config
+        };
+        let cancel = CancellationToken::new();
+        let thread_cancel = cancel.clone();
+        let thread_request_count = request_count.clone();
+        // cancel only once first attempt was answered, timer alone races with slow test runs
+        std::thread::spawn(move || {
+            while thread_request_count.load(std::sync::atomic::Ordering::SeqCst) == 0 {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            thread_cancel.cancel();
+        });
+        let mut attempt_list = Vec::new();
+        let err = rt.block_on(index.download_with_progress(&[broken_url], &config, &mut |progress| attempt_list.push(progress.attempt), &cancel)).unwrap_err();
+        assert!(matches!(err, BlockIndexError::Cancelled), "{}", err);
+        // first attempt was answered, second never started
+        assert_eq!(attempt_list, vec![0]);
+        assert_eq!(request_count.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(index.block_list, INDEX.block_list[10..]);
+        Ok(())
+    }
+
+    #[test]
+    fn shared_block_index() -> Result<(), Box<dyn std::error::Error>> {
+        let rt = Runtime::new()?;
+        let shared = std::sync::Arc::new(SharedBlockIndex::new(index_without_tip(100)));
+        let mut new_tip_rx = shared.subscribe();
+
+        // snapshot does not see later swaps
+        let old = shared.snapshot();
+        shared.replace(index_without_tip(50));
+        assert_eq!(old.get_tip_height(), Some(4207));
+        assert_eq!(shared.load().get_tip_height(), Some(4257));
+        let new_tip = new_tip_rx.try_recv()?;
+        assert_eq!(new_tip, NewTip {
+            prev_tip_height: Some(4207),
+            height: 4257,
+            indep_hash: INDEX.get_by_height_indep_hash(4257).unwrap(),
+            weave_size: INDEX.get_by_height_weave_size(4257).unwrap(),
+        });
+        // same tip is not published
+        shared.replace(index_without_tip(50));
+        assert!(new_tip_rx.try_recv().is_err());
+
+        let peer_ranged_url = format!("http://{}", spawn_test_server(peer_ranged_handler));
+        let config = fast_download_config();
+        assert_eq!(rt.block_on(shared.update(std::slice::from_ref(&peer_ranged_url), &config))?.map(|v| v.height), Some(4307));
+        assert_eq!(shared.load().block_list, INDEX.block_list);
+        assert_eq!(new_tip_rx.try_recv()?.prev_tip_height, Some(4257));
+        // up to date, index is not copied
+        let current = shared.snapshot();
+        assert_eq!(rt.block_on(shared.update(std::slice::from_ref(&peer_ranged_url), &config))?, None);
+        assert!(std::sync::Arc::ptr_eq(&current, &shared.snapshot()));
+
+        // local tail is on other branch, full download and reorg
+        let mut forked = BlockIndex3Json::new();
+        forked._load_from_original_format(forked_block_list())?;
+        shared.replace(forked);
+        assert_eq!(new_tip_rx.try_recv()?.height, 3528);
+        assert_eq!(rt.block_on(shared.update(std::slice::from_ref(&peer_ranged_url), &config))?.map(|v| v.height), Some(4307));
+        assert_eq!(shared.load().block_list, INDEX.block_list);
+        new_tip_rx.try_recv()?;
+
+        // forked peer is behind local tip, current is kept
+        let peer_forked_behind_url = format!("http://{}", spawn_test_server(peer_forked_behind_handler));
+        shared.replace(index_without_tip(10));
+        new_tip_rx.try_recv()?;
+        let current = shared.snapshot();
+        assert_eq!(rt.block_on(shared.update(std::slice::from_ref(&peer_forked_behind_url), &config))?, None);
+        assert!(std::sync::Arc::ptr_eq(&current, &shared.snapshot()));
+        assert!(new_tip_rx.try_recv().is_err());
+
+        // background updater
+        let cancel = CancellationToken::new();
+        let updater_config = UpdaterConfig { interval: std::time::Duration::from_millis(10), download: fast_download_config() };
+        let new_tip = rt.block_on(async {
+            let handle = shared.spawn_updater(vec![dead_peer_url(), peer_ranged_url], updater_config, cancel.clone());
+            let new_tip = tokio::time::timeout(std::time::Duration::from_secs(10), new_tip_rx.recv()).await;
+            cancel.cancel();
+            handle.await.unwrap();
+            new_tip
+        })??;
+        assert_eq!((new_tip.prev_tip_height, new_tip.height), (Some(4297), 4307));
+        assert_eq!(shared.load().block_list, INDEX.block_list);
+        assert_eq!(shared.last_update_err(), None);
+
+        // errors are kept, updater goes on
+        let cancel = CancellationToken::new();
+        let updater_config = UpdaterConfig { interval: std::time::Duration::from_millis(10), download: fast_download_config() };
+        rt.block_on(async {
+            let handle = shared.spawn_updater(vec![dead_peer_url()], updater_config, cancel.clone());
+            while shared.last_update_err().is_none() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            cancel.cancel();
+            handle.await.unwrap();
+        });
+        assert_eq!(shared.load().block_list, INDEX.block_list);
+        Ok(())
+    }
+
+    #[test]
+    fn test_download_consensus() -> Result<(), Box<dyn std::error::Error>> {
+        let honest_url = format!("http://{}", spawn_test_server(peer_full_handler));
+        let honest2_url = format!("http://{}", spawn_test_server(peer_ranged_handler));
+        let forked_url = format!("http://{}", spawn_test_server(peer_forked_handler));
+        let lagging_url = format!("http://{}", spawn_test_server(peer_lagging_handler));
+        let dead_url = dead_peer_url();
+        let rt = Runtime::new()?;
+        let config = ConsensusConfig::default();
+
+        let mut index = BlockIndex3Json::new();
+        let res = rt.block_on(index.download_consensus(&[honest_url.clone(), forked_url.clone(), dead_url.clone(), honest2_url.clone(), lagging_url.clone()], &config))?;
+        assert_eq!(index.block_list, INDEX.block_list);
+        assert_eq!(res.tip_height, 4307);
+        assert_eq!(res.sample_height_list.len(), 32);
+        let status_list: Vec<&ConsensusPeerStatus> = res.peer_res_list.iter().map(|v| &v.status).collect();
+        assert_eq!(status_list[0], &ConsensusPeerStatus::Agreed);
+        assert_eq!(status_list[1], &ConsensusPeerStatus::Disagreed { height: 4307 });
+        assert!(matches!(status_list[2], ConsensusPeerStatus::Failed { .. }));
+        assert_eq!(status_list[3], &ConsensusPeerStatus::Agreed);
+        assert_eq!(status_list[4], &ConsensusPeerStatus::Lagging { tip_height: 4299 });
+
+        // quorum 3 needs lagging peer, index is truncated to its tip
+        let config3 = ConsensusConfig { quorum: 3, ..ConsensusConfig::default() };
+        let mut index = BlockIndex3Json::new();
+        let res = rt.block_on(index.download_consensus(&[honest_url.clone(), lagging_url.clone(), honest2_url.clone()], &config3))?;
+        assert_eq!(res.tip_height, 4299);
+        assert_eq!(index.block_list, INDEX.block_list[8..]);
+
+        // tie
+        let mut index = BlockIndex3Json::new();
+        let err = rt.block_on(index.download_consensus(&[honest_url.clone(), forked_url.clone(), dead_url], &config)).unwrap_err();
+        let err = match err {
+            BlockIndexError::NoQuorum(err) => err,
+            err => panic!("unexpected error {}", err),
+        };
+        assert_eq!(err.peer_res_list[1].status, ConsensusPeerStatus::Disagreed { height: 4307 });
+        assert!(matches!(err.peer_res_list[2].status, ConsensusPeerStatus::Failed { .. }));
+        assert!(index.block_list.is_empty());
+
+        // 2 vs 2 tie, both groups reach quorum
+        let forked2_url = format!("http://{}", spawn_test_server(peer_forked_handler));
+        let mut index = BlockIndex3Json::new();
+        let res = rt.block_on(index.download_consensus(&[honest_url.clone(), forked_url.clone(), honest2_url.clone(), forked2_url], &config));
+        assert!(matches!(res, Err(BlockIndexError::NoQuorum(_))));
+        assert!(index.block_list.is_empty());
+
+        let mut index = BlockIndex3Json::new();
+        assert!(matches!(rt.block_on(index.download_consensus(std::slice::from_ref(&honest_url), &config)), Err(BlockIndexError::NoQuorum(_))));
+        let config0 = ConsensusConfig { quorum: 0, ..ConsensusConfig::default() };
+        assert!(matches!(rt.block_on(index.download_consensus(&[honest_url], &config0)), Err(BlockIndexError::InvalidArgument(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_from_reader() -> Result<(), Box<dyn std::error::Error>> {
+        let cont = fs::read("../test_asset/block_index_slice")?;
+        let mut index = BlockIndex3Json::new();
+        index.load_from_reader(std::io::Cursor::new(&cont))?;
+        assert_eq!(index.block_list, INDEX.block_list);
+
+        let mut index = BlockIndex3Json::new();
+        run_test(index.load_from_async_reader(&cont[..]))?;
+        assert_eq!(index.block_list, INDEX.block_list);
+
+        // entries split at every possible place
+        let mut parser = crate::json_stream::BlockIndex3JsonStreamParser::new();
+        for ch in cont.chunks(7) {
+            parser.feed(ch)?;
+        }
+        assert_eq!(parser.finish()?, INDEX.block_list);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_from_reader_invalid() {
+        fn load(cont: &str) -> Result<(), BlockIndexError> {
+            BlockIndex3Json::new().load_from_reader(cont.as_bytes())
+        }
+        let entry = |weave_size: &str| -> String {
+            format!("{{\"tx_root\":\"{}\",\"weave_size\":\"{}\",\"hash\":\"{}\"}}", BLOCK_0_ORIG.tx_root, weave_size, BLOCK_0_ORIG.hash)
+        };
+
+        assert!(load(&format!(" [ {} , {} ]\n", entry("2"), entry("1"))).is_ok());
+        assert!(load("[]").is_err());
+        assert!(load("").is_err());
+        // truncated
+        assert!(load(&format!("[{},{}", entry("2"), entry("1"))).is_err());
+        assert!(load(&format!("[{},", entry("2"))).is_err());
+        // trailing garbage
+        assert!(load(&format!("[{}]]", entry("2"))).is_err());
+        assert!(load(&format!("[{},]", entry("2"))).is_err());
+        // weave_size grows towards tip only
+        let err = load(&format!("[{},{}]", entry("1"), entry("2"))).unwrap_err();
+        assert!(matches!(err, BlockIndexError::NonMonotonicWeaveSize { idx: 0, prev_weave_size: 2, weave_size: 1 }));
+        assert_eq!(err.to_string(), "json[0] prev_weave_size > weave_size; 2 > 1");
+        let err = load(&format!("[{},{}]", entry("1"), entry("x"))).unwrap_err();
+        assert!(matches!(err, BlockIndexError::BadWeaveSize { idx: 1 }));
+        assert_eq!(err.to_string(), "json[1].weave_size is not decimal");
+        let err = load(&format!("[{}]", entry("1").replace(&BLOCK_0_ORIG.hash, "abc"))).unwrap_err();
+        assert!(matches!(err, BlockIndexError::BadBase64 { idx: 0, field: "hash", len: 64 }));
+        let err = load(&format!("[{}]", entry("1").replace(&BLOCK_0_ORIG.tx_root, "abc"))).unwrap_err();
+        assert!(matches!(err, BlockIndexError::BadBase64 { idx: 0, field: "tx_root", len: 43 }));
+        assert!(load("[{\"tx_root\":{}}]").is_err());
+    }
+
 }
@@ -0,0 +1,163 @@
+use std::fs;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use openssl::hash::{Hasher, MessageDigest};
+
+use chunk::*;
+use types::*;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  validate_path
+//  purpose - iterative walker vs recursive one it replaced
+//  recursive walker is baseline copied verbatim (per level .to_vec() of rest, i128 note decoding),
+//  it is not crate API (no depth cap, panics on malformed path)
+//  synthetic trees of growing depth + real data_path of test_asset/chunk_1
+//  run: cargo bench -p chunk
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+// Same result as ValidateRes of crate, fields of ValidateRes are private
+// only read through Debug
+#[allow(dead_code)]
+#[derive(Debug)]
+struct ValidateRes {
+    root: ChunkRootType,
+    start: WeaveOffsetType,
+    end: WeaveOffsetType,
+}
+
+fn sha256(buf: &[u8]) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+    let mut hasher = Hasher::new(MessageDigest::sha256())?;
+    hasher.update(buf)?;
+    Ok(hasher.finish()?.as_ref().to_vec())
+}
+
+fn sha256_list(list: &[&[u8]]) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+    let mut hasher = Hasher::new(MessageDigest::sha256())?;
+    for buf in list {
+        hasher.update(buf)?;
+    }
+    Ok(hasher.finish()?.as_ref().to_vec())
+}
+
+fn validate_path_recursive(
+    root: ChunkRootType,
+    mut offset: WeaveOffsetType,
+    block_size: WeaveSizeType,
+    any_path: &ChunkPathType,
+) -> Option<ValidateRes> {
+    if block_size <= 0 {
+        return None;
+    }
+    if offset >= block_size {
+        offset = block_size - 1;
+    }
+    if offset < 0 {
+        offset = 0;
+    }
+    let left: WeaveOffsetType = 0;
+    let right = block_size;
+    _validate_path_lr(root, offset, left, right, any_path)
+}
+
+// returns kept as in baseline
+#[allow(clippy::needless_return)]
+fn _validate_path_lr(
+    tx_root: ChunkRootType,
+    offset: WeaveOffsetType,
+    left: WeaveOffsetType,
+    right: WeaveOffsetType,
+    tx_path: &ChunkPathType,
+) -> Option<ValidateRes> {
+    if tx_path.len() == CHUNKROOT_LENGTH + NOTE_LENGTH {
+        let data = &tx_path[0..CHUNKROOT_LENGTH];
+        let note = &tx_path[CHUNKROOT_LENGTH..];
+        let expd_id = sha256_list(&[&sha256(data).unwrap(), &sha256(note).unwrap()]).unwrap();
+
+        if tx_root != expd_id.as_slice() {
+            return None;
+        }
+        // TEMP SOLUTION
+        // Will break when we will hit i128 capacity
+        // let note_bn = i128::from_be_bytes(note.try_into().unwrap());
+        let note_bn = i128::from_be_bytes((&note[16..]).try_into().unwrap());
+        return Some(ValidateRes {
+            root: data.try_into().unwrap(),
+            start: left,
+            end: std::cmp::max(std::cmp::min(right, note_bn), left + 1),
+        });
+    } else {
+        let l = &tx_path[0..CHUNKROOT_LENGTH];
+        let r = &tx_path[CHUNKROOT_LENGTH..2 * CHUNKROOT_LENGTH];
+        let note = &tx_path[2 * CHUNKROOT_LENGTH..2 * CHUNKROOT_LENGTH + NOTE_LENGTH];
+        let rest = &tx_path[2 * CHUNKROOT_LENGTH + NOTE_LENGTH..].to_vec();
+        let expd_id = sha256_list(&[&sha256(l).unwrap(), &sha256(r).unwrap(), &sha256(note).unwrap()]).unwrap();
+
+        if tx_root != expd_id.as_slice() {
+            return None;
+        }
+
+        // TEMP SOLUTION
+        // Will break when we will hit i128 capacity
+        // let note_bn = i128::from_be_bytes(note.try_into().unwrap());
+        let note_bn = i128::from_be_bytes((&note[16..]).try_into().unwrap());
+        if offset < note_bn {
+            return _validate_path_lr(l.try_into().unwrap(), offset, left, std::cmp::min(right, note_bn), rest);
+        } else {
+            return _validate_path_lr(r.try_into().unwrap(), offset, std::cmp::max(left, note_bn), right, rest);
+        }
+    }
+}
+
+// Both walkers must agree before they are compared by speed
+// baseline has no error kinds, so crate result is compared as Option, Debug of both is same text
+fn assert_same(root: ChunkRootType, offset: WeaveOffsetType, block_size: WeaveSizeType, path: &ChunkPathType) {
+    let res = format!("{:?}", validate_path(root, offset, block_size, path).ok());
+    let res_recursive = format!("{:?}", validate_path_recursive(root, offset, block_size, path));
+    assert_eq!(res, res_recursive);
+}
+
+fn bench_validate_path(c: &mut Criterion) {
+    let mut group = c.benchmark_group("validate_path");
+    for depth in [1, 8, 16] {
+        let leaf_count: WeaveOffsetType = 1 << depth;
+        let leaf_list = (0..leaf_count).map(|i| MerkleLeaf { data: vec![i as u8; 32], end: (i + 1) * 10 }).collect();
+        let tree = build_merkle_tree(leaf_list).unwrap();
+        let block_size = leaf_count * 10;
+        let idx = tree.path_list.len() / 2;
+        let path = &tree.path_list[idx];
+        let offset = idx as WeaveOffsetType * 10;
+        assert_same(tree.root, offset, block_size, path);
+        assert_same(tree.root, 0, block_size, path);
+        group.bench_with_input(BenchmarkId::new("iterative", depth), path, |b, path| {
+            b.iter(|| validate_path(tree.root, black_box(offset), block_size, black_box(path)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("recursive", depth), path, |b, path| {
+            b.iter(|| validate_path_recursive(tree.root, black_box(offset), block_size, black_box(path)).unwrap())
+        });
+    }
+
+    let chunk1_json: ChunkJson = serde_json::from_str(&fs::read_to_string("../test_asset/chunk_1.json").unwrap()).unwrap();
+    let data_path = chunk_from_json(&chunk1_json).unwrap().data_path;
+    let data_root = data_path_root(&data_path);
+    for offset in [0, 599057] {
+        assert_same(data_root, offset, 599058, &data_path);
+    }
+    group.bench_function("iterative/chunk_1", |b| {
+        b.iter(|| validate_path(data_root, black_box(0), 599058, black_box(&data_path)).unwrap())
+    });
+    group.bench_function("recursive/chunk_1", |b| {
+        b.iter(|| validate_path_recursive(data_root, black_box(0), 599058, black_box(&data_path)).unwrap())
+    });
+    group.finish();
+}
+
+// root id is hash of first path node
+fn data_path_root(data_path: &[u8]) -> ChunkRootType {
+    let mut hasher = openssl::sha::Sha256::new();
+    for part in data_path[..96].chunks(32) {
+        hasher.update(&openssl::sha::sha256(part));
+    }
+    hasher.finish()
+}
+
+criterion_group!(benches, bench_validate_path);
+criterion_main!(benches);
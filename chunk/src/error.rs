@@ -0,0 +1,70 @@
+use std::error::Error;
+use std::fmt;
+
+use types::*;
+
+#[derive(Debug)]
+pub enum ChunkValidationError {
+    // field is chunk json field name (chunk, tx_path, data_path)
+    BadBase64 { field: &'static str, err: data_encoding::DecodeError },
+    UnknownPacking(String),
+    // chunk offset is outside of block index
+    OffsetOutOfRange { offset: WeaveOffsetType },
+    HeightOutOfRange { height: HeightType },
+    MissingTxRoot { offset: WeaveOffsetType },
+    BadBlockSize { block_size: WeaveSizeType },
+    // depth 0 is root, path ends before leaf
+    PathTooShort { depth: usize, len: usize },
+    // above MERKLE_PATH_MAX_SIZE
+    PathTooLong { len: usize },
+    // sha256 of path node at depth does not match expected hash
+    HashMismatch { depth: usize },
+    // note at depth is above WeaveOffsetType range, hash matched so proof is crafted
+    NoteOverflow { depth: usize },
+    // chunk can not be unpacked for hash check
+    UnsupportedPacking { packing: &'static str },
+    // chunk_size is from data_path leaf range
+    ChunkSizeMismatch { chunk_size: WeaveOffsetType, len: usize },
+    // sha256 of unpacked chunk is not data_path leaf
+    ChunkIdMismatch,
+    EmptyMerkleTree,
+    Hash(openssl::error::ErrorStack),
+}
+
+impl fmt::Display for ChunkValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkValidationError::BadBase64 { field, err } => write!(f, "Failed to decode {}: {}", field, err),
+            ChunkValidationError::UnknownPacking(packing) => write!(f, "unknown packing {}", packing),
+            ChunkValidationError::OffsetOutOfRange { offset } => write!(f, "chunk offset {} is not in block index", offset),
+            ChunkValidationError::HeightOutOfRange { height } => write!(f, "height {} is not in block index", height),
+            ChunkValidationError::MissingTxRoot { offset } => write!(f, "block at chunk offset {} has no tx_root", offset),
+            ChunkValidationError::BadBlockSize { block_size } => write!(f, "block_size {} <= 0", block_size),
+            ChunkValidationError::PathTooShort { depth, len } => write!(f, "path is too short at depth {}; {} bytes left", depth, len),
+            ChunkValidationError::PathTooLong { len } => write!(f, "path is too long; {} bytes", len),
+            ChunkValidationError::HashMismatch { depth } => write!(f, "hash mismatch at merkle depth {}", depth),
+            ChunkValidationError::NoteOverflow { depth } => write!(f, "note at merkle depth {} does not fit offset", depth),
+            ChunkValidationError::UnsupportedPacking { packing } => write!(f, "unpacking {} is not supported", packing),
+            ChunkValidationError::ChunkSizeMismatch { chunk_size, len } => write!(f, "chunk is {} bytes, data_path says {}", len, chunk_size),
+            ChunkValidationError::ChunkIdMismatch => write!(f, "sha256 of chunk does not match data_path leaf"),
+            ChunkValidationError::EmptyMerkleTree => write!(f, "merkle tree needs at least one leaf"),
+            ChunkValidationError::Hash(err) => write!(f, "sha256: {}", err),
+        }
+    }
+}
+
+impl Error for ChunkValidationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ChunkValidationError::BadBase64 { err, .. } => Some(err),
+            ChunkValidationError::Hash(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<openssl::error::ErrorStack> for ChunkValidationError {
+    fn from(err: openssl::error::ErrorStack) -> Self {
+        ChunkValidationError::Hash(err)
+    }
+}
@@ -1,9 +1,15 @@
-use std::error::Error;
 use data_encoding::BASE64URL_NOPAD;
 use types::*;
 use serde::Deserialize;
 use openssl::hash::{Hasher, MessageDigest};
 
+mod error;
+pub use error::*;
+mod merkle;
+pub use merkle::*;
+mod tx_root;
+pub use tx_root::*;
+
 fn sha256(buf: &[u8]) -> Result<Vec<u8>, openssl::error::ErrorStack> {
     let mut hasher = Hasher::new(MessageDigest::sha256())?;
     hasher.update(buf)?;
@@ -26,11 +32,19 @@ pub enum Packing {
 }
 
 impl Packing {
-    pub fn from_str(s: &str) -> Result<Packing, Box<dyn Error>> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Packing, ChunkValidationError> {
         match s {
             "unpacked" => Ok(Packing::Unpacked),
             "spora_2_5" => Ok(Packing::Spora25),
-            _ => Err(Box::from(format!("unknown packing {}", s))),
+            _ => Err(ChunkValidationError::UnknownPacking(s.to_string())),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Packing::Unpacked => "unpacked",
+            Packing::Spora25 => "spora_2_5",
         }
     }
 }
@@ -51,12 +65,13 @@ pub struct ChunkJson {
     pub packing: String,
 }
 
-pub fn chunk_from_json(chunk_json: &ChunkJson) -> Result<Chunk, Box<dyn Error>> {
-    let chunk_vec = BASE64URL_NOPAD.decode(chunk_json.chunk.as_bytes())?;
+pub fn chunk_from_json(chunk_json: &ChunkJson) -> Result<Chunk, ChunkValidationError> {
+    let chunk_vec = BASE64URL_NOPAD.decode(chunk_json.chunk.as_bytes())
+        .map_err(|err| ChunkValidationError::BadBase64 { field: "chunk", err })?;
     let tx_path = BASE64URL_NOPAD.decode(chunk_json.tx_path.as_bytes())
-        .map_err(|e| format!("Failed to decode tx_path: {:?}", e))?;
+        .map_err(|err| ChunkValidationError::BadBase64 { field: "tx_path", err })?;
     let data_path = BASE64URL_NOPAD.decode(chunk_json.data_path.as_bytes())
-        .map_err(|e| format!("Failed to decode data_path: {:?}", e))?;
+        .map_err(|err| ChunkValidationError::BadBase64 { field: "data_path", err })?;
     let packing = Packing::from_str(&chunk_json.packing)?;
 
     Ok(Chunk {
@@ -69,7 +84,7 @@ pub fn chunk_from_json(chunk_json: &ChunkJson) -> Result<Chunk, Box<dyn Error>>
 
 
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct ValidateTxPathRes {
     pub data_root: ChunkRootType,
     pub tx_start: WeaveOffsetType,
@@ -77,29 +92,38 @@ pub struct ValidateTxPathRes {
     pub recall_bucket_offset: WeaveOffsetType,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct ValidateDataPathRes {
+    // data_path leaf, sha256 of unpacked chunk
+    pub chunk_id: ChunkRootType,
     pub chunk_size: WeaveOffsetType,
     pub offset_diff: WeaveOffsetType,
 }
 
 
+// chunk_offset is 1-based, same as GET /chunk/{offset}: block owns offsets in (block_start, block_end]
 pub fn validate_tx_path(
     tx_path: &ChunkPathType,
-    mut chunk_offset: WeaveOffsetType,
+    chunk_offset: WeaveOffsetType,
     block_index3: &dyn BlockIndex3,
     strict_data_split_threshold: WeaveOffsetType,
-) -> Option<ValidateTxPathRes> {
-    let block_index_entity = block_index3.get_by_chunk_offset_full(chunk_offset)?;
-    if chunk_offset >= strict_data_split_threshold {
-        let diff = chunk_offset - strict_data_split_threshold;
-        chunk_offset = strict_data_split_threshold + (diff / DATA_CHUNK_SIZE) * DATA_CHUNK_SIZE;
+) -> Result<ValidateTxPathRes, ChunkValidationError> {
+    let block_index_entity = block_index3.get_by_chunk_offset_full(chunk_offset)
+        .ok_or(ChunkValidationError::OffsetOutOfRange { offset: chunk_offset })?;
+    let tx_root = block_index_entity.tx_root
+        .ok_or(ChunkValidationError::MissingTxRoot { offset: chunk_offset })?;
+    // 0-based byte in weave, above threshold chunks are aligned, any byte of chunk is its first one
+    let mut byte_offset = chunk_offset - 1;
+    if byte_offset >= strict_data_split_threshold {
+        let diff = byte_offset - strict_data_split_threshold;
+        byte_offset = strict_data_split_threshold + (diff / DATA_CHUNK_SIZE) * DATA_CHUNK_SIZE;
     }
-    
-    let recall_bucket_offset = chunk_offset - block_index_entity.weave_size;
-    let ret = validate_path(block_index_entity.tx_root.unwrap(), recall_bucket_offset, block_index_entity.block_size, tx_path)?;
-    
-    Some(ValidateTxPathRes {
+
+    let block_start = block_index_entity.weave_size - block_index_entity.block_size;
+    let recall_bucket_offset = byte_offset - block_start;
+    let ret = validate_path(tx_root, recall_bucket_offset, block_index_entity.block_size, tx_path)?;
+
+    Ok(ValidateTxPathRes {
         data_root: ret.root,
         tx_start: ret.start,
         tx_end: ret.end,
@@ -107,90 +131,192 @@ pub fn validate_tx_path(
     })
 }
 
-pub fn validate_data_path(data_path: &ChunkPathType, val_res: ValidateTxPathRes) -> Option<ValidateDataPathRes> {
+pub fn validate_data_path(data_path: &ChunkPathType, val_res: ValidateTxPathRes) -> Result<ValidateDataPathRes, ChunkValidationError> {
     let tx_size = val_res.tx_end - val_res.tx_start;
     let recall_chunk_offset = val_res.recall_bucket_offset - val_res.tx_start;
-    let ret = validate_path(val_res.data_root, recall_chunk_offset, tx_size, data_path);
-    ret.map(|res| {
-        ValidateDataPathRes {
-            chunk_size: res.end - res.start,
-            offset_diff: res.start - recall_chunk_offset,
-        }
+    let res = validate_path(val_res.data_root, recall_chunk_offset, tx_size, data_path)?;
+    Ok(ValidateDataPathRes {
+        chunk_id: res.root,
+        chunk_size: res.end - res.start,
+        offset_diff: res.start - recall_chunk_offset,
     })
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ValidateChunkStage {
+    TxPath,
+    DataPath,
+    Unpack,
+    ChunkSize,
+    ChunkId,
+}
+impl ValidateChunkStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValidateChunkStage::TxPath => "tx_path",
+            ValidateChunkStage::DataPath => "data_path",
+            ValidateChunkStage::Unpack => "unpack",
+            ValidateChunkStage::ChunkSize => "chunk_size",
+            ValidateChunkStage::ChunkId => "chunk_id",
+        }
+    }
+}
+
+// Everything found before first failed stage, stages run in ValidateChunkStage order
+#[derive(Debug)]
+pub struct ValidateChunkRes {
+    pub tx_path: Option<ValidateTxPathRes>,
+    pub data_path: Option<ValidateDataPathRes>,
+    // sha256 of unpacked chunk
+    pub chunk_id: Option<ChunkRootType>,
+    pub err: Option<(ValidateChunkStage, ChunkValidationError)>,
+}
+impl ValidateChunkRes {
+    pub fn is_valid(&self) -> bool {
+        self.err.is_none()
+    }
+}
+
+// Full check of chunk answered by peer for offset: tx_path against block tx_root,
+// data_path against data_root, chunk bytes against data_path leaf
+pub fn validate_chunk(
+    chunk: &Chunk,
+    chunk_offset: WeaveOffsetType,
+    block_index3: &dyn BlockIndex3,
+    strict_data_split_threshold: WeaveOffsetType,
+) -> ValidateChunkRes {
+    let mut ret = ValidateChunkRes {
+        tx_path: None,
+        data_path: None,
+        chunk_id: None,
+        err: None,
+    };
+    if let Err(err) = _validate_chunk(chunk, chunk_offset, block_index3, strict_data_split_threshold, &mut ret) {
+        ret.err = Some(err);
+    }
+    ret
+}
+
+fn _validate_chunk(
+    chunk: &Chunk,
+    chunk_offset: WeaveOffsetType,
+    block_index3: &dyn BlockIndex3,
+    strict_data_split_threshold: WeaveOffsetType,
+    ret: &mut ValidateChunkRes,
+) -> Result<(), (ValidateChunkStage, ChunkValidationError)> {
+    let tx_res = validate_tx_path(&chunk.tx_path, chunk_offset, block_index3, strict_data_split_threshold)
+        .map_err(|err| (ValidateChunkStage::TxPath, err))?;
+    ret.tx_path = Some(tx_res.clone());
+    let data_res = validate_data_path(&chunk.data_path, tx_res)
+        .map_err(|err| (ValidateChunkStage::DataPath, err))?;
+    ret.data_path = Some(data_res.clone());
+
+    let unpacked = match chunk.packing {
+        Packing::Unpacked => &chunk.chunk,
+        // TODO unpack
+        Packing::Spora25 => {
+            let err = ChunkValidationError::UnsupportedPacking { packing: chunk.packing.as_str() };
+            return Err((ValidateChunkStage::Unpack, err));
+        }
+    };
+    if unpacked.len() as WeaveOffsetType != data_res.chunk_size {
+        let err = ChunkValidationError::ChunkSizeMismatch { chunk_size: data_res.chunk_size, len: unpacked.len() };
+        return Err((ValidateChunkStage::ChunkSize, err));
+    }
+    let chunk_id: ChunkRootType = sha256(unpacked)
+        .map_err(|err| (ValidateChunkStage::ChunkId, err.into()))?
+        .try_into()
+        .unwrap();
+    ret.chunk_id = Some(chunk_id);
+    if chunk_id != data_res.chunk_id {
+        return Err((ValidateChunkStage::ChunkId, ChunkValidationError::ChunkIdMismatch));
+    }
+    Ok(())
+}
+
+#[derive(PartialEq, Debug)]
 pub struct ValidateRes {
     root: ChunkRootType,
     start: WeaveOffsetType,
     end: WeaveOffsetType,
 }
 
+const PATH_BRANCH_SIZE: usize = 2 * CHUNKROOT_LENGTH + NOTE_LENGTH;
+const PATH_LEAF_SIZE: usize = CHUNKROOT_LENGTH + NOTE_LENGTH;
+// 2^64 leaves is far above any real tree, longer path is garbage
+pub const MERKLE_PATH_MAX_DEPTH: usize = 64;
+pub const MERKLE_PATH_MAX_SIZE: usize = MERKLE_PATH_MAX_DEPTH * PATH_BRANCH_SIZE + PATH_LEAF_SIZE;
+
 pub fn validate_path(
     root: ChunkRootType,
-    mut offset: WeaveOffsetType,
+    offset: WeaveOffsetType,
     block_size: WeaveSizeType,
-    any_path: &ChunkPathType,
-) -> Option<ValidateRes> {
+    any_path: &[u8],
+) -> Result<ValidateRes, ChunkValidationError> {
     if block_size <= 0 {
-        return None;
-    }
-    if offset >= block_size {
-        offset = block_size - 1;
-    }
-    if offset < 0 {
-        offset = 0;
+        return Err(ChunkValidationError::BadBlockSize { block_size });
     }
-    let left: WeaveOffsetType = 0;
-    let right = block_size;
-    _validate_path_lr(root, offset, left, right, any_path)
+    let offset = offset.clamp(0, block_size - 1);
+    _validate_path_lr(root, offset, 0, block_size, any_path)
 }
 
+// sha256(sha256(part_0) | sha256(part_1) | ...), on stack
+fn _hash_node(part_list: &[&[u8]]) -> ChunkRootType {
+    let mut hasher = openssl::sha::Sha256::new();
+    for part in part_list {
+        hasher.update(&openssl::sha::sha256(part));
+    }
+    hasher.finish()
+}
 
+// Walks path from root to leaf in loop, path is only borrowed, no heap allocations
+// node at depth d is checked against id from node at depth d - 1 (root for d = 0)
 fn _validate_path_lr(
-    tx_root: ChunkRootType,
+    root: ChunkRootType,
     offset: WeaveOffsetType,
-    left: WeaveOffsetType,
-    right: WeaveOffsetType,
-    tx_path: &ChunkPathType,
-) -> Option<ValidateRes> {
-    if tx_path.len() == CHUNKROOT_LENGTH + NOTE_LENGTH {
-        let data = &tx_path[0..CHUNKROOT_LENGTH];
-        let note = &tx_path[CHUNKROOT_LENGTH..];
-        let expd_id = sha256_list(&[&sha256(data).unwrap(), &sha256(note).unwrap()]).unwrap();
-
-        if tx_root != expd_id.as_slice() {
-            return None;
+    mut left: WeaveOffsetType,
+    mut right: WeaveOffsetType,
+    path: &[u8],
+) -> Result<ValidateRes, ChunkValidationError> {
+    if path.len() > MERKLE_PATH_MAX_SIZE {
+        return Err(ChunkValidationError::PathTooLong { len: path.len() });
+    }
+    let mut expected_id = root;
+    let mut rest = path;
+    // size cap leaves leaf at depth MERKLE_PATH_MAX_DEPTH at most
+    for depth in 0..=MERKLE_PATH_MAX_DEPTH {
+        if rest.len() == PATH_LEAF_SIZE {
+            let (data, note) = rest.split_at(CHUNKROOT_LENGTH);
+            if _hash_node(&[data, note]) != expected_id {
+                return Err(ChunkValidationError::HashMismatch { depth });
+            }
+            let note_bn = note_to_offset(note).ok_or(ChunkValidationError::NoteOverflow { depth })?;
+            return Ok(ValidateRes {
+                root: data.try_into().unwrap(),
+                start: left,
+                end: std::cmp::max(std::cmp::min(right, note_bn), left + 1),
+            });
         }
-        // TEMP SOLUTION
-        // Will break when we will hit i128 capacity
-        // let note_bn = i128::from_be_bytes(note.try_into().unwrap());
-        let note_bn = i128::from_be_bytes((&note[16..]).try_into().unwrap());
-        return Some(ValidateRes {
-            root: data.try_into().unwrap(),
-            start: left,
-            end: std::cmp::max(std::cmp::min(right, note_bn), left + 1),
-        });
-    } else {
-        let l = &tx_path[0..CHUNKROOT_LENGTH];
-        let r = &tx_path[CHUNKROOT_LENGTH..2 * CHUNKROOT_LENGTH];
-        let note = &tx_path[2 * CHUNKROOT_LENGTH..2 * CHUNKROOT_LENGTH + NOTE_LENGTH];
-        let rest = &tx_path[2 * CHUNKROOT_LENGTH + NOTE_LENGTH..].to_vec();
-        let expd_id = sha256_list(&[&sha256(l).unwrap(), &sha256(r).unwrap(), &sha256(note).unwrap()]).unwrap();
-
-        if tx_root != expd_id.as_slice() {
-            return None;
+        if rest.len() < PATH_BRANCH_SIZE {
+            return Err(ChunkValidationError::PathTooShort { depth, len: rest.len() });
         }
-
-        // TEMP SOLUTION
-        // Will break when we will hit i128 capacity
-        // let note_bn = i128::from_be_bytes(note.try_into().unwrap());
-        let note_bn = i128::from_be_bytes((&note[16..]).try_into().unwrap());
+        let (l, tail) = rest.split_at(CHUNKROOT_LENGTH);
+        let (r, tail) = tail.split_at(CHUNKROOT_LENGTH);
+        let (note, tail) = tail.split_at(NOTE_LENGTH);
+        if _hash_node(&[l, r, note]) != expected_id {
+            return Err(ChunkValidationError::HashMismatch { depth });
+        }
+        let note_bn = note_to_offset(note).ok_or(ChunkValidationError::NoteOverflow { depth })?;
         if offset < note_bn {
-            return _validate_path_lr(l.try_into().unwrap(), offset, left, std::cmp::min(right, note_bn), rest);
+            expected_id = l.try_into().unwrap();
+            right = std::cmp::min(right, note_bn);
         } else {
-            return _validate_path_lr(r.try_into().unwrap(), offset, std::cmp::max(left, note_bn), right, rest);
+            expected_id = r.try_into().unwrap();
+            left = std::cmp::max(left, note_bn);
         }
+        rest = tail;
     }
+    Err(ChunkValidationError::PathTooLong { len: path.len() })
 }
 
 
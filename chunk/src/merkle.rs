@@ -0,0 +1,212 @@
+use types::*;
+
+use crate::{sha256, sha256_list, ChunkValidationError};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  Merkle
+//  purpose - build data_root and data_path from raw data (uploads, test fixtures)
+//  chunking and tree shape follow arweave-js (merkle.ts), so roots match network ones
+//  leaf id = sha256(sha256(data) | sha256(note)), note = leaf end offset, data may be empty
+//  branch id = sha256(sha256(left id) | sha256(right id) | sha256(note)), note = left end offset
+//  layer is paired left to right, odd last node goes one layer up as is
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub const MAX_CHUNK_SIZE: usize = DATA_CHUNK_SIZE as usize;
+pub const MIN_CHUNK_SIZE: usize = 32 * 1024;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MerkleLeaf {
+    // chunk_id for data tree, data_root for tx tree, empty for tx padding
+    pub data: Vec<u8>,
+    // exclusive, leaf covers (previous leaf end, end]
+    pub end: WeaveOffsetType,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct MerkleTree {
+    pub root: ChunkRootType,
+    pub leaf_list: Vec<MerkleLeaf>,
+    // one per leaf, same order
+    pub path_list: Vec<ChunkPathType>,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct DataMerkleTree {
+    pub data_root: ChunkRootType,
+    pub data_size: WeaveOffsetType,
+    // chunk i is data[start..end]
+    pub chunk_list: Vec<MerkleLeaf>,
+    pub data_path_list: Vec<ChunkPathType>,
+}
+impl DataMerkleTree {
+    pub fn chunk_start(&self, idx: usize) -> WeaveOffsetType {
+        match idx {
+            0 => 0,
+            _ => self.chunk_list[idx - 1].end,
+        }
+    }
+}
+
+const OFFSET_LENGTH: usize = std::mem::size_of::<WeaveOffsetType>();
+
+pub fn note_from_offset(offset: WeaveOffsetType) -> ChunkNoteType {
+    let mut note = [0; NOTE_LENGTH];
+    note[NOTE_LENGTH - OFFSET_LENGTH..].copy_from_slice(&offset.to_be_bytes());
+    note
+}
+
+// note is 256 bit BE unsigned; None if it does not fit WeaveOffsetType (high bytes or sign bit set)
+pub fn note_to_offset(note: &[u8]) -> Option<WeaveOffsetType> {
+    if note.len() != NOTE_LENGTH {
+        return None;
+    }
+    let (high, low) = note.split_at(NOTE_LENGTH - OFFSET_LENGTH);
+    if high.iter().any(|v| *v != 0) {
+        return None;
+    }
+    let offset = WeaveOffsetType::from_be_bytes(low.try_into().unwrap());
+    if offset < 0 {
+        return None;
+    }
+    Some(offset)
+}
+
+// Sizes of chunks data is split to
+// full MAX_CHUNK_SIZE chunks, but if tail after next full chunk would be < MIN_CHUNK_SIZE
+// last two chunks are split in halves (first one gets odd byte)
+// data of multiple of MAX_CHUNK_SIZE ends with 0 sized chunk, it counts for data_root
+pub fn chunk_size_list(data_size: usize) -> Vec<usize> {
+    let mut ret = Vec::with_capacity(data_size / MAX_CHUNK_SIZE + 1);
+    let mut rest = data_size;
+    while rest >= MAX_CHUNK_SIZE {
+        let mut chunk_size = MAX_CHUNK_SIZE;
+        let next_chunk_size = rest - MAX_CHUNK_SIZE;
+        if next_chunk_size > 0 && next_chunk_size < MIN_CHUNK_SIZE {
+            chunk_size = rest.div_ceil(2);
+        }
+        ret.push(chunk_size);
+        rest -= chunk_size;
+    }
+    ret.push(rest);
+    ret
+}
+
+enum MerkleNode {
+    Leaf {
+        id: ChunkRootType,
+        leaf_idx: usize,
+    },
+    Branch {
+        id: ChunkRootType,
+        left: Box<MerkleNode>,
+        right: Box<MerkleNode>,
+        // left end
+        note: ChunkNoteType,
+    },
+}
+impl MerkleNode {
+    fn id(&self) -> &ChunkRootType {
+        match self {
+            MerkleNode::Leaf { id, .. } => id,
+            MerkleNode::Branch { id, .. } => id,
+        }
+    }
+}
+
+fn _hash(list: &[&[u8]]) -> Result<ChunkRootType, ChunkValidationError> {
+    let mut hash_list = Vec::with_capacity(list.len());
+    for buf in list {
+        hash_list.push(sha256(buf)?);
+    }
+    let hash_ref_list: Vec<&[u8]> = hash_list.iter().map(|v| v.as_slice()).collect();
+    Ok(sha256_list(&hash_ref_list)?.try_into().unwrap())
+}
+
+// prefix is path from root to node
+fn _collect_path_list(node: &MerkleNode, leaf_list: &[MerkleLeaf], prefix: &mut Vec<u8>, path_list: &mut [ChunkPathType]) {
+    match node {
+        MerkleNode::Leaf { leaf_idx, .. } => {
+            let leaf = &leaf_list[*leaf_idx];
+            let mut path = Vec::with_capacity(prefix.len() + leaf.data.len() + NOTE_LENGTH);
+            path.extend_from_slice(prefix);
+            path.extend_from_slice(&leaf.data);
+            path.extend_from_slice(&note_from_offset(leaf.end));
+            path_list[*leaf_idx] = path;
+        }
+        MerkleNode::Branch { left, right, note, .. } => {
+            let len = prefix.len();
+            prefix.extend_from_slice(left.id());
+            prefix.extend_from_slice(right.id());
+            prefix.extend_from_slice(note);
+            _collect_path_list(left, leaf_list, prefix, path_list);
+            _collect_path_list(right, leaf_list, prefix, path_list);
+            prefix.truncate(len);
+        }
+    }
+}
+
+// leaf_list ends must be ascending
+pub fn build_merkle_tree(leaf_list: Vec<MerkleLeaf>) -> Result<MerkleTree, ChunkValidationError> {
+    if leaf_list.is_empty() {
+        return Err(ChunkValidationError::EmptyMerkleTree);
+    }
+    // (node, end of rightmost leaf)
+    let mut layer: Vec<(MerkleNode, WeaveOffsetType)> = Vec::with_capacity(leaf_list.len());
+    for (leaf_idx, leaf) in leaf_list.iter().enumerate() {
+        let id = _hash(&[&leaf.data, &note_from_offset(leaf.end)])?;
+        layer.push((MerkleNode::Leaf { id, leaf_idx }, leaf.end));
+    }
+    while layer.len() > 1 {
+        let mut next_layer = Vec::with_capacity(layer.len().div_ceil(2));
+        let mut iter = layer.into_iter();
+        while let Some((left, left_end)) = iter.next() {
+            match iter.next() {
+                Some((right, right_end)) => {
+                    let note = note_from_offset(left_end);
+                    let id = _hash(&[left.id(), right.id(), &note])?;
+                    next_layer.push((MerkleNode::Branch { id, left: Box::new(left), right: Box::new(right), note }, right_end));
+                }
+                None => next_layer.push((left, left_end)),
+            }
+        }
+        layer = next_layer;
+    }
+    let (root, _) = layer.pop().unwrap();
+    let mut path_list = vec![Vec::new(); leaf_list.len()];
+    _collect_path_list(&root, &leaf_list, &mut Vec::new(), &mut path_list);
+    Ok(MerkleTree {
+        root: *root.id(),
+        leaf_list,
+        path_list,
+    })
+}
+
+// Chunks data, builds tree; 0 sized tail chunk is part of data_root,
+// but has no data_path and is not in chunk_list (nothing to upload)
+pub fn build_data_merkle_tree(data: &[u8]) -> Result<DataMerkleTree, ChunkValidationError> {
+    let mut leaf_list = Vec::new();
+    let mut start = 0;
+    for chunk_size in chunk_size_list(data.len()) {
+        let end = start + chunk_size;
+        leaf_list.push(MerkleLeaf {
+            data: sha256(&data[start..end])?,
+            end: end as WeaveOffsetType,
+        });
+        start = end;
+    }
+    let MerkleTree { root, mut leaf_list, mut path_list } = build_merkle_tree(leaf_list)?;
+    if leaf_list.len() > 1 && leaf_list[leaf_list.len() - 1].end == leaf_list[leaf_list.len() - 2].end {
+        leaf_list.pop();
+        path_list.pop();
+    }
+    if data.is_empty() {
+        leaf_list.clear();
+        path_list.clear();
+    }
+    Ok(DataMerkleTree {
+        data_root: root,
+        data_size: data.len() as WeaveOffsetType,
+        chunk_list: leaf_list,
+        data_path_list: path_list,
+    })
+}
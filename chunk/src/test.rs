@@ -10,7 +10,7 @@ mod chunk_test {
     static INDEX: Lazy<BlockIndex3Json> = Lazy::new(|| {
         let path = "../test_asset/block_index_slice";
         let mut index = BlockIndex3Json::new();
-        index.load_sync(&path).unwrap();
+        index.load_sync(path).unwrap();
         index
     });
 
@@ -25,8 +25,8 @@ mod chunk_test {
 
     #[test]
     fn test_chunk_from_json() {
-        let chunk1_json: ChunkJson = serde_json::from_str(&*CHUNK1_JSON).unwrap();
-        let chunk2_json: ChunkJson = serde_json::from_str(&*CHUNK2_JSON).unwrap();
+        let chunk1_json: ChunkJson = serde_json::from_str(&CHUNK1_JSON).unwrap();
+        let chunk2_json: ChunkJson = serde_json::from_str(&CHUNK2_JSON).unwrap();
 
         let _chunk1_unpacked = chunk_from_json(&chunk1_json).unwrap();
         let _chunk2_unpacked = chunk_from_json(&chunk2_json).unwrap();
@@ -35,8 +35,8 @@ mod chunk_test {
 
     #[test]
     fn test_validate_tx_path() {
-        let chunk1_json: ChunkJson = serde_json::from_str(&*CHUNK1_JSON).unwrap();
-        let chunk2_json: ChunkJson = serde_json::from_str(&*CHUNK2_JSON).unwrap();
+        let chunk1_json: ChunkJson = serde_json::from_str(&CHUNK1_JSON).unwrap();
+        let chunk2_json: ChunkJson = serde_json::from_str(&CHUNK2_JSON).unwrap();
         
         let chunk1_unpacked = chunk_from_json(&chunk1_json).unwrap();
         let chunk2_unpacked = chunk_from_json(&chunk2_json).unwrap();
@@ -52,7 +52,7 @@ mod chunk_test {
             data_root: data_root_chunk1,
             tx_start: 0,
             tx_end: 599058,
-            recall_bucket_offset: -599057, // it's ok for block 0
+            recall_bucket_offset: 0,
         });
 
         let tx_val_res_chunk2 = validate_tx_path(&chunk2_unpacked.tx_path, CHUNK2_OFFSET, &*INDEX, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD)
@@ -66,15 +66,15 @@ mod chunk_test {
             data_root: data_root_chunk2,
             tx_start: 0,
             tx_end: 439971,
-            recall_bucket_offset: -439970, // it's NOT ok for block N
+            recall_bucket_offset: 0,
         });
     }
 
     #[test]
     fn test_validate_data_path() {
         // Retrieving chunk1 and chunk2 from previous example
-        let chunk1_json: ChunkJson = serde_json::from_str(&*CHUNK1_JSON).unwrap();
-        let chunk2_json: ChunkJson = serde_json::from_str(&*CHUNK2_JSON).unwrap();
+        let chunk1_json: ChunkJson = serde_json::from_str(&CHUNK1_JSON).unwrap();
+        let chunk2_json: ChunkJson = serde_json::from_str(&CHUNK2_JSON).unwrap();
         
         let chunk1_unpacked = chunk_from_json(&chunk1_json).unwrap();
         let chunk2_unpacked = chunk_from_json(&chunk2_json).unwrap();
@@ -84,8 +84,9 @@ mod chunk_test {
         let data_val_res_chunk1 = validate_data_path(&chunk1_unpacked.data_path, tx_val_res_chunk1)
             .expect("!data_val_res");
         assert_eq!(data_val_res_chunk1, ValidateDataPathRes {
+            chunk_id: BASE64URL_NOPAD.decode(b"h3J2SLTxwfuNpJVy8rHrdnqCIGYv32c37EWCCjUeiK4").unwrap().try_into().unwrap(),
             chunk_size: 262144,
-            offset_diff: 599057,
+            offset_diff: 0,
         });
 
         let tx_val_res_chunk2 = validate_tx_path(&chunk2_unpacked.tx_path, CHUNK2_OFFSET, &*INDEX, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD)
@@ -94,9 +95,361 @@ mod chunk_test {
             .expect("!data_val_res");
         println!("{:?}", data_val_res_chunk2);
         assert_eq!(data_val_res_chunk2, ValidateDataPathRes {
+            chunk_id: BASE64URL_NOPAD.decode(b"wslYzrJb_EL8ebZipBv1sb-naKznJoA87aEEr_G_2_g").unwrap().try_into().unwrap(),
             chunk_size: 262144,
-            offset_diff: 439970,
+            offset_diff: 0,
         });
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_validate_errors() {
+        let chunk1_json: ChunkJson = serde_json::from_str(&CHUNK1_JSON).unwrap();
+        let chunk1_unpacked = chunk_from_json(&chunk1_json).unwrap();
+
+        let bad_json = ChunkJson { tx_path: "!".into(), ..serde_json::from_str(&CHUNK1_JSON).unwrap() };
+        assert!(matches!(chunk_from_json(&bad_json), Err(ChunkValidationError::BadBase64 { field: "tx_path", .. })));
+        let bad_json = ChunkJson { packing: "spora_2_7".into(), ..serde_json::from_str(&CHUNK1_JSON).unwrap() };
+        assert!(matches!(chunk_from_json(&bad_json), Err(ChunkValidationError::UnknownPacking(_))));
+
+        let tip_offset = INDEX.get_by_height_weave_size(INDEX.get_tip_height().unwrap()).unwrap();
+        let res = validate_tx_path(&chunk1_unpacked.tx_path, tip_offset + 1, &*INDEX, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD);
+        assert!(matches!(res, Err(ChunkValidationError::OffsetOutOfRange { .. })));
+
+        let mut tx_path = chunk1_unpacked.tx_path.clone();
+        tx_path[0] ^= 1;
+        let res = validate_tx_path(&tx_path, CHUNK1_OFFSET, &*INDEX, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD);
+        assert!(matches!(res, Err(ChunkValidationError::HashMismatch { depth: 0 })));
+
+        let mut tx_path = chunk1_unpacked.tx_path.clone();
+        tx_path.pop();
+        let res = validate_tx_path(&tx_path, CHUNK1_OFFSET, &*INDEX, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD);
+        assert!(matches!(res, Err(ChunkValidationError::PathTooShort { depth: 0, .. })));
+
+        // first node is fine, child node is corrupted
+        let tx_val_res = validate_tx_path(&chunk1_unpacked.tx_path, CHUNK1_OFFSET, &*INDEX, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD).unwrap();
+        let mut data_path = chunk1_unpacked.data_path.clone();
+        data_path[2 * CHUNKROOT_LENGTH + NOTE_LENGTH] ^= 1;
+        let res = validate_data_path(&data_path, tx_val_res);
+        assert!(matches!(res, Err(ChunkValidationError::HashMismatch { depth: 1 })));
+
+        // block without txs
+        let empty_block = |weave_size: &str| format!("{{\"tx_root\":\"\",\"weave_size\":\"{}\",\"hash\":\"{}\"}}", weave_size, "A".repeat(64));
+        let mut index = BlockIndex3Json::new();
+        index.load_from_reader(format!("[{},{}]", empty_block("200"), empty_block("0")).as_bytes()).unwrap();
+        let res = validate_tx_path(&chunk1_unpacked.tx_path, 100, &index, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD);
+        assert!(matches!(res, Err(ChunkValidationError::MissingTxRoot { offset: 100 })));
+
+        assert!(matches!(validate_path([0; 32], 0, 0, &chunk1_unpacked.tx_path), Err(ChunkValidationError::BadBlockSize { block_size: 0 })));
+    }
+
+    #[test]
+    fn test_validate_chunk() {
+        let chunk_json: ChunkJson = serde_json::from_str(&CHUNK2_JSON).unwrap();
+        let mut chunk = chunk_from_json(&chunk_json).unwrap();
+
+        let res = validate_chunk(&chunk, CHUNK2_OFFSET, &*INDEX, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD);
+        assert!(res.is_valid(), "{:?}", res.err);
+        assert_eq!(res.tx_path.unwrap().tx_end, 439971);
+        let data_path = res.data_path.unwrap();
+        assert_eq!(res.chunk_id, Some(data_path.chunk_id));
+        assert_eq!(data_path.chunk_size, 262144);
+
+        // valid proof, garbage data
+        chunk.chunk[0] ^= 1;
+        let res = validate_chunk(&chunk, CHUNK2_OFFSET, &*INDEX, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD);
+        assert!(matches!(res.err, Some((ValidateChunkStage::ChunkId, ChunkValidationError::ChunkIdMismatch))));
+        assert!(res.data_path.is_some());
+        assert_ne!(res.chunk_id, Some(res.data_path.unwrap().chunk_id));
+
+        chunk.chunk.pop();
+        let res = validate_chunk(&chunk, CHUNK2_OFFSET, &*INDEX, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD);
+        assert!(matches!(res.err, Some((ValidateChunkStage::ChunkSize, ChunkValidationError::ChunkSizeMismatch { chunk_size: 262144, len: 262143 }))));
+        assert_eq!(res.chunk_id, None);
+
+        chunk.packing = Packing::Spora25;
+        let res = validate_chunk(&chunk, CHUNK2_OFFSET, &*INDEX, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD);
+        assert!(matches!(res.err, Some((ValidateChunkStage::Unpack, ChunkValidationError::UnsupportedPacking { packing: "spora_2_5" }))));
+
+        // other chunk proof at this offset
+        let chunk1_json: ChunkJson = serde_json::from_str(&CHUNK1_JSON).unwrap();
+        let chunk1 = chunk_from_json(&chunk1_json).unwrap();
+        let res = validate_chunk(&chunk1, CHUNK2_OFFSET, &*INDEX, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD);
+        assert!(matches!(res.err, Some((ValidateChunkStage::TxPath, ChunkValidationError::HashMismatch { depth: 0 }))));
+        assert!(res.tx_path.is_none());
+
+        let mut chunk1 = chunk1;
+        chunk1.data_path[0] ^= 1;
+        let res = validate_chunk(&chunk1, CHUNK1_OFFSET, &*INDEX, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD);
+        assert!(matches!(res.err, Some((ValidateChunkStage::DataPath, ChunkValidationError::HashMismatch { depth: 0 }))));
+        assert!(res.tx_path.is_some() && res.data_path.is_none());
+        assert_eq!(ValidateChunkStage::DataPath.as_str(), "data_path");
+    }
+
+    #[test]
+    fn test_chunk_size_list() {
+        assert_eq!(chunk_size_list(0), vec![0]);
+        assert_eq!(chunk_size_list(100), vec![100]);
+        // data_root of full chunks has 0 sized tail
+        assert_eq!(chunk_size_list(2 * MAX_CHUNK_SIZE), vec![MAX_CHUNK_SIZE, MAX_CHUNK_SIZE, 0]);
+        assert_eq!(chunk_size_list(MAX_CHUNK_SIZE + MIN_CHUNK_SIZE), vec![MAX_CHUNK_SIZE, MIN_CHUNK_SIZE]);
+        // tail below min, last two are rebalanced
+        assert_eq!(chunk_size_list(MAX_CHUNK_SIZE + MIN_CHUNK_SIZE - 1), vec![(MAX_CHUNK_SIZE + MIN_CHUNK_SIZE) / 2, (MAX_CHUNK_SIZE + MIN_CHUNK_SIZE) / 2 - 1]);
+        assert_eq!(chunk_size_list(2 * MAX_CHUNK_SIZE + 1), vec![MAX_CHUNK_SIZE, MAX_CHUNK_SIZE / 2 + 1, MAX_CHUNK_SIZE / 2]);
+        assert_eq!(chunk_size_list(599058), vec![MAX_CHUNK_SIZE, MAX_CHUNK_SIZE, 599058 - 2 * MAX_CHUNK_SIZE]);
+    }
+
+    #[test]
+    fn test_data_merkle_tree() {
+        let data_of = |size: usize| -> Vec<u8> { (0..size).map(|i| (i * 7 + i / 251) as u8).collect() };
+        for size in [1, 1000, MAX_CHUNK_SIZE, MAX_CHUNK_SIZE + 1000, 3 * MAX_CHUNK_SIZE, 3 * MAX_CHUNK_SIZE + 5, 599058] {
+            let data = data_of(size);
+            let tree = build_data_merkle_tree(&data).unwrap();
+            assert_eq!(tree.data_size, size as WeaveOffsetType);
+            assert_eq!(tree.chunk_list.len(), tree.data_path_list.len());
+            assert_eq!(tree.chunk_list.last().unwrap().end, size as WeaveOffsetType);
+            for (idx, (chunk, data_path)) in tree.chunk_list.iter().zip(&tree.data_path_list).enumerate() {
+                let start = tree.chunk_start(idx);
+                assert_eq!(sha256(&data[start as usize..chunk.end as usize]).unwrap(), chunk.data);
+                for offset in [start, (start + chunk.end) / 2, chunk.end - 1] {
+                    let res = validate_path(tree.data_root, offset, tree.data_size, data_path).unwrap();
+                    assert_eq!((res.root.to_vec(), res.start, res.end), (chunk.data.clone(), start, chunk.end), "size {} chunk {} offset {}", size, idx, offset);
+                }
+                // path leads to its chunk only
+                if tree.chunk_list.len() > 1 {
+                    let other_offset = if idx == 0 { chunk.end } else { start - 1 };
+                    assert!(validate_path(tree.data_root, other_offset, tree.data_size, data_path).is_err());
+                }
+            }
+        }
+        // different data, different root
+        let mut data = data_of(1000);
+        let data_root = build_data_merkle_tree(&data).unwrap().data_root;
+        data[999] ^= 1;
+        assert_ne!(build_data_merkle_tree(&data).unwrap().data_root, data_root);
+
+        let tree = build_data_merkle_tree(&[]).unwrap();
+        assert!(tree.chunk_list.is_empty() && tree.data_path_list.is_empty());
+        assert!(matches!(build_merkle_tree(Vec::new()), Err(ChunkValidationError::EmptyMerkleTree)));
+    }
+
+    #[test]
+    fn test_data_merkle_tree_shape() {
+        // tx of chunk_1 is 599058 bytes, 3 chunks; built path has same layout and offsets
+        let chunk_json: ChunkJson = serde_json::from_str(&CHUNK1_JSON).unwrap();
+        let chunk = chunk_from_json(&chunk_json).unwrap();
+        let mut data = vec![0; 599058];
+        data[..chunk.chunk.len()].copy_from_slice(&chunk.chunk);
+        let tree = build_data_merkle_tree(&data).unwrap();
+        let data_path = &tree.data_path_list[0];
+        assert_eq!(data_path.len(), chunk.data_path.len());
+        let note_range_list = [64..96, 160..192, 224..256];
+        for note_range in note_range_list {
+            assert_eq!(data_path[note_range.clone()], chunk.data_path[note_range]);
+        }
+        // leaf is chunk id of real chunk, siblings are of other (zero) data
+        assert_eq!(data_path[192..224], chunk.data_path[192..224]);
+        assert_eq!(note_from_offset(262144)[..], chunk.data_path[224..256]);
+    }
+
+    #[test]
+    fn test_tx_root() {
+        let data_root_of = |s: &str| -> ChunkRootType { BASE64URL_NOPAD.decode(s.as_bytes()).unwrap().try_into().unwrap() };
+        // blocks 82 and 3527 of test slice have one tx each
+        for (height, data_root, data_size, chunk_json) in [
+            (82, "kuMLOSJKG7O4NmSBY9KZ2PjU-5O4UBNFl_-kF9FnW7w", 599058, &*CHUNK1_JSON),
+            (3527, "nyGPB30FMq2Bx7TRNXInl6rKFSN4W5na9RycpGbT5IA", 439971, &*CHUNK2_JSON),
+        ] {
+            let tx = BlockTx { id: [0; 32], format: 2, data_root: data_root_of(data_root), data_size };
+            let check = check_block_tx_root(&*INDEX, height, std::slice::from_ref(&tx)).unwrap();
+            assert!(check.is_valid(), "{:?}", check);
+            let chunk = chunk_from_json(&serde_json::from_str(chunk_json).unwrap()).unwrap();
+            assert_eq!(build_tx_root_tree(std::slice::from_ref(&tx), height).unwrap().tx_path_list, vec![chunk.tx_path]);
+
+            let check = check_block_tx_root(&*INDEX, height, &[BlockTx { data_size: data_size - 1, ..tx.clone() }]).unwrap();
+            assert!(!check.is_valid());
+            assert_eq!(check.expected_block_size, data_size);
+            assert!(!check_block_tx_root(&*INDEX, height, &[tx.clone(), tx]).unwrap().is_valid());
+        }
+        // empty block
+        let check = check_block_tx_root(&*INDEX, 83, &[]).unwrap();
+        assert!(check.is_valid());
+        assert_eq!(check.tx_root, None);
+        assert!(matches!(check_block_tx_root(&*INDEX, 4308, &[]), Err(ChunkValidationError::HeightOutOfRange { height: 4308 })));
+    }
+
+    #[test]
+    fn test_tx_path() {
+        let tx_of = |id: u8, format: u8, data_size: WeaveSizeType| BlockTx { id: [id; 32], format, data_root: [id ^ 0x5a; 32], data_size };
+        let tx_list = vec![tx_of(3, 2, 1000), tx_of(1, 2, 300000), tx_of(9, 1, 5), tx_of(2, 2, 0), tx_of(7, 2, 600000)];
+
+        // node order is (format, id)
+        let tree = build_tx_root_tree(&tx_list, 1).unwrap();
+        assert_eq!(tree.tx_list.iter().map(|v| v.id[0]).collect::<Vec<_>>(), vec![9, 1, 2, 3, 7]);
+        assert_eq!(tree.tx_end_list, vec![5, 300005, 300005, 301005, 901005]);
+        assert_eq!(tree.block_size, 901005);
+        assert_eq!(build_tx_root(&tx_list[..], 1).unwrap(), tree.tx_root);
+        let mut reversed = tx_list.clone();
+        reversed.reverse();
+        assert_eq!(build_tx_root(&reversed, 1).unwrap(), tree.tx_root);
+
+        // from 2.5 every tx is padded to chunk size
+        let padded = build_tx_root_tree(&tx_list, FORK_2_5_HEIGHT).unwrap();
+        assert_eq!(padded.tx_end_list, vec![5, 262144 + 300000, 3 * 262144, 3 * 262144 + 1000, 4 * 262144 + 600000]);
+        assert_eq!(padded.block_size, 7 * 262144);
+        assert_ne!(padded.tx_root, tree.tx_root);
+
+        // padding leaf by hand, as ar_block:generate_size_tagged_list_from_txs
+        // leaves (a, 1000), (<<>>, 262144), (b, 524288); first two pair up, third goes up as is
+        let hash = |list: &[&[u8]]| -> ChunkRootType {
+            let hash_list: Vec<Vec<u8>> = list.iter().map(|v| sha256(v).unwrap()).collect();
+            let hash_ref_list: Vec<&[u8]> = hash_list.iter().map(|v| v.as_slice()).collect();
+            sha256_list(&hash_ref_list).unwrap().try_into().unwrap()
+        };
+        let (tx_a, tx_b) = (tx_of(1, 2, 1000), tx_of(2, 2, 262144));
+        let leaf_a = hash(&[&tx_a.data_root, &note_from_offset(1000)]);
+        let leaf_padding = hash(&[&[], &note_from_offset(262144)]);
+        let leaf_b = hash(&[&tx_b.data_root, &note_from_offset(524288)]);
+        let branch = hash(&[&leaf_a, &leaf_padding, &note_from_offset(1000)]);
+        let tx_root = hash(&[&branch, &leaf_b, &note_from_offset(262144)]);
+        let ab_tree = build_tx_root_tree(&[tx_b.clone(), tx_a.clone()], FORK_2_5_HEIGHT).unwrap();
+        assert_eq!(ab_tree.tx_root, Some(tx_root));
+        assert_eq!(ab_tree.tx_path_list, vec![
+            [&branch[..], &leaf_b, &note_from_offset(262144), &leaf_a, &leaf_padding, &note_from_offset(1000), &tx_a.data_root, &note_from_offset(1000)].concat(),
+            [&branch[..], &leaf_b, &note_from_offset(262144), &tx_b.data_root, &note_from_offset(524288)].concat(),
+        ]);
+
+        for tree in [tree, padded] {
+            // genesis without txs and block with txs of tree
+            let tx_root = BASE64URL_NOPAD.encode(&tree.tx_root.unwrap());
+            let block = |tx_root: &str, weave_size: WeaveSizeType| format!("{{\"tx_root\":\"{}\",\"weave_size\":\"{}\",\"hash\":\"{}\"}}", tx_root, weave_size, "A".repeat(64));
+            let mut index = BlockIndex3Json::new();
+            index.load_from_reader(format!("[{},{}]", block(&tx_root, 1000 + tree.block_size), block("", 1000)).as_bytes()).unwrap();
+            // height 1 is before 2.5, padded tree is of other block
+            assert_eq!(check_block_tx_root(&index, 1, &tx_list).unwrap().is_valid(), tree.block_size == 901005);
+
+            for (idx, tx) in tree.tx_list.iter().enumerate() {
+                let tx_end = tree.tx_end_list[idx];
+                let tx_start = tx_end - tx.data_size;
+                if tx.data_size == 0 {
+                    continue;
+                }
+                for offset in [tx_start + 1, tx_end] {
+                    let res = validate_tx_path(&tree.tx_path_list[idx], 1000 + offset, &index, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD).unwrap();
+                    assert_eq!((res.data_root, res.tx_start, res.tx_end), (tx.data_root, tx_start, tx_end));
+                }
+                // padding after tx is proven by padding leaf, not by tx_path
+                if tree.block_size != 901005 && tx_end % DATA_CHUNK_SIZE != 0 {
+                    assert!(validate_tx_path(&tree.tx_path_list[idx], 1000 + tx_end + 1, &index, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD).is_err());
+                }
+                // path of other tx
+                let other_idx = (idx + 1) % tree.tx_list.len();
+                assert!(validate_tx_path(&tree.tx_path_list[other_idx], 1000 + tx_end, &index, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD).is_err());
+            }
+        }
+        assert_eq!(build_tx_root(&[], FORK_2_5_HEIGHT).unwrap(), None);
+    }
+
+    #[test]
+    fn test_validate_path_note() {
+        let hash = |list: &[&[u8]]| -> ChunkRootType {
+            let hash_list: Vec<Vec<u8>> = list.iter().map(|v| sha256(v).unwrap()).collect();
+            let hash_ref_list: Vec<&[u8]> = hash_list.iter().map(|v| v.as_slice()).collect();
+            sha256_list(&hash_ref_list).unwrap().try_into().unwrap()
+        };
+        // leaf only proof, note is whatever prover wants, hash still matches
+        let leaf_proof = |note: &ChunkNoteType| -> (ChunkRootType, ChunkPathType) {
+            let data = [7; 32];
+            (hash(&[&data, note]), [&data[..], &note[..]].concat())
+        };
+        let (root, path) = leaf_proof(&note_from_offset(50));
+        let res = validate_path(root, 10, 100, &path).unwrap();
+        assert_eq!((res.root, res.start, res.end), ([7; 32], 0, 50));
+
+        // 2^128 + 50 was read as 50
+        let mut note = note_from_offset(50);
+        note[15] = 1;
+        let (root, path) = leaf_proof(&note);
+        assert!(matches!(validate_path(root, 10, 100, &path), Err(ChunkValidationError::NoteOverflow { depth: 0 })));
+        note[15] = 0;
+        note[0] = 0x80;
+        let (root, path) = leaf_proof(&note);
+        assert!(matches!(validate_path(root, 10, 100, &path), Err(ChunkValidationError::NoteOverflow { depth: 0 })));
+        // fits 128 bit unsigned, not i128
+        let mut note = [0; 32];
+        note[16] = 0x80;
+        let (root, path) = leaf_proof(&note);
+        assert!(matches!(validate_path(root, 10, 100, &path), Err(ChunkValidationError::NoteOverflow { depth: 0 })));
+        let mut note = [0; 32];
+        note[16] = 0x7f;
+        let (root, path) = leaf_proof(&note);
+        assert_eq!(validate_path(root, 10, 100, &path).unwrap().end, 100);
+
+        // branch note with high byte, read as 1 it would send offset 10 right
+        let (left_id, right_id) = ([1; 32], [2; 32]);
+        let mut note = note_from_offset(1);
+        note[3] = 1;
+        let root = hash(&[&left_id, &right_id, &note]);
+        let mut path = [&left_id[..], &right_id[..], &note[..]].concat();
+        path.extend_from_slice(&leaf_proof(&note_from_offset(100)).1);
+        assert!(matches!(validate_path(root, 10, 100, &path), Err(ChunkValidationError::NoteOverflow { depth: 0 })));
+
+        // child branch
+        let mut data_path = chunk_from_json(&serde_json::from_str(&CHUNK1_JSON).unwrap()).unwrap().data_path;
+        let note_range = 2 * CHUNKROOT_LENGTH + NOTE_LENGTH + 2 * CHUNKROOT_LENGTH..2 * (2 * CHUNKROOT_LENGTH + NOTE_LENGTH);
+        data_path[note_range.start] = 1;
+        let child_id = hash(&[&data_path[96..128], &data_path[128..160], &data_path[note_range.clone()]]);
+        data_path[..32].copy_from_slice(&child_id);
+        let root = hash(&[&data_path[..32], &data_path[32..64], &data_path[64..96]]);
+        assert!(matches!(validate_path(root, 0, 599058, &data_path), Err(ChunkValidationError::NoteOverflow { depth: 1 })));
+
+        assert_eq!(note_to_offset(&note_from_offset(599058)), Some(599058));
+        assert_eq!(note_to_offset(&[0; 31]), None);
+    }
+
+    #[test]
+    fn test_validate_path_iterative() {
+        for leaf_count in [1, 2, 3, 5, 8, 13] {
+            let leaf_list: Vec<MerkleLeaf> = (0..leaf_count).map(|i| MerkleLeaf { data: vec![i as u8; 32], end: 10 * (i + 1) }).collect();
+            let tree = build_merkle_tree(leaf_list).unwrap();
+            let block_size = 10 * leaf_count;
+            for (i, path) in tree.path_list.iter().enumerate() {
+                let offset = 10 * i as WeaveOffsetType + 5;
+                let res = validate_path(tree.root, offset, block_size, path).unwrap();
+                assert_eq!(res, ValidateRes { root: [i as u8; 32], start: 10 * i as WeaveOffsetType, end: offset + 5 });
+                // offset of other leaf turns off this path at some branch
+                let other_offset = block_size - 1 - offset;
+                match validate_path(tree.root, other_offset, block_size, path) {
+                    Ok(other_res) => {
+                        assert_eq!(other_offset / 10, i as WeaveOffsetType);
+                        assert_eq!(other_res, res);
+                    }
+                    Err(err) => assert!(matches!(err, ChunkValidationError::HashMismatch { depth } if depth > 0), "{}", err),
+                }
+                let mut path = path.clone();
+                *path.last_mut().unwrap() ^= 1;
+                assert!(matches!(validate_path(tree.root, offset, block_size, &path), Err(ChunkValidationError::HashMismatch { .. })));
+                path.pop();
+                assert!(matches!(validate_path(tree.root, offset, block_size, &path), Err(ChunkValidationError::PathTooShort { .. })));
+            }
+        }
+
+        let chunk1 = chunk_from_json(&serde_json::from_str(&CHUNK1_JSON).unwrap()).unwrap();
+        let tx_val_res = validate_tx_path(&chunk1.tx_path, CHUNK1_OFFSET, &*INDEX, DEFAULT_STRICT_DATA_SPLIT_THRESHOLD).unwrap();
+        let data_root = tx_val_res.data_root;
+        let data_val_res = validate_data_path(&chunk1.data_path, tx_val_res).unwrap();
+        assert_eq!(data_val_res.chunk_id, validate_path(data_root, 0, 599058, &chunk1.data_path).unwrap().root);
+        // offsets are clamped to block, path proves first chunk only
+        for offset in [-1, 0, 262143] {
+            assert_eq!(validate_path(data_root, offset, 599058, &chunk1.data_path).unwrap().root, data_val_res.chunk_id);
+        }
+        for offset in [262144, 599057, 599058, 1 << 100] {
+            assert!(matches!(validate_path(data_root, offset, 599058, &chunk1.data_path), Err(ChunkValidationError::HashMismatch { .. })));
+        }
+
+        // garbage above size cap is rejected before hashing
+        let path = vec![0; MERKLE_PATH_MAX_SIZE + 1];
+        assert!(matches!(validate_path([0; 32], 0, 100, &path), Err(ChunkValidationError::PathTooLong { len }) if len == MERKLE_PATH_MAX_SIZE + 1));
+        let path = vec![0; MERKLE_PATH_MAX_SIZE];
+        assert!(matches!(validate_path([0; 32], 0, 100, &path), Err(ChunkValidationError::HashMismatch { depth: 0 })));
+    }
+}
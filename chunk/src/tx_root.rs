@@ -0,0 +1,119 @@
+use types::*;
+
+use crate::{build_merkle_tree, ChunkValidationError, MerkleLeaf};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  TxRoot
+//  purpose - rebuild block tx_root and tx_paths from (data_root, data_size) of block txs
+//  same layout as arweave node (ar_block:generate_size_tagged_list_from_txs):
+//  txs sorted by (format, id), each tx is leaf data_root with note = tx end in block
+//  from fork 2.5 every tx takes data_size rounded up to DATA_CHUNK_SIZE, non-zero padding
+//  is own leaf right after tx: empty data root, note = padded tx end
+//  block without txs has no tx_root
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub const FORK_2_5_HEIGHT: HeightType = 812970;
+
+pub type TxIdType = [u8; 32];
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlockTx {
+    pub id: TxIdType,
+    pub format: u8,
+    pub data_root: ChunkRootType,
+    pub data_size: WeaveSizeType,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct TxRootTree {
+    // None if block has no txs
+    pub tx_root: Option<TxRootType>,
+    // weave_size increase of block
+    pub block_size: WeaveSizeType,
+    // in block order
+    pub tx_list: Vec<BlockTx>,
+    // tx i data is (tx_end_list[i] - tx_list[i].data_size, tx_end_list[i]] in block,
+    // its tx_path proves same range; padding leaves have no tx_path
+    pub tx_end_list: Vec<WeaveOffsetType>,
+    pub tx_path_list: Vec<ChunkPathType>,
+}
+
+pub fn weave_size_increase(data_size: WeaveSizeType, height: HeightType) -> WeaveSizeType {
+    if height < FORK_2_5_HEIGHT || data_size == 0 {
+        return data_size;
+    }
+    ((data_size - 1) / DATA_CHUNK_SIZE + 1) * DATA_CHUNK_SIZE
+}
+
+pub fn build_tx_root_tree(tx_list: &[BlockTx], height: HeightType) -> Result<TxRootTree, ChunkValidationError> {
+    let mut tx_list = tx_list.to_vec();
+    tx_list.sort_by_key(|v| (v.format, v.id));
+    let mut leaf_list = Vec::with_capacity(2 * tx_list.len());
+    let mut tx_leaf_idx_list = Vec::with_capacity(tx_list.len());
+    let mut tx_end_list = Vec::with_capacity(tx_list.len());
+    let mut block_size = 0;
+    for tx in &tx_list {
+        let tx_end = block_size + tx.data_size;
+        tx_leaf_idx_list.push(leaf_list.len());
+        tx_end_list.push(tx_end);
+        leaf_list.push(MerkleLeaf {
+            data: tx.data_root.to_vec(),
+            end: tx_end,
+        });
+        block_size += weave_size_increase(tx.data_size, height);
+        if block_size > tx_end {
+            leaf_list.push(MerkleLeaf {
+                data: Vec::new(),
+                end: block_size,
+            });
+        }
+    }
+    if leaf_list.is_empty() {
+        return Ok(TxRootTree {
+            tx_root: None,
+            block_size,
+            tx_list,
+            tx_end_list,
+            tx_path_list: Vec::new(),
+        });
+    }
+    let mut tree = build_merkle_tree(leaf_list)?;
+    let tx_path_list = tx_leaf_idx_list.into_iter().map(|idx| std::mem::take(&mut tree.path_list[idx])).collect();
+    Ok(TxRootTree {
+        tx_root: Some(tree.root),
+        block_size,
+        tx_list,
+        tx_end_list,
+        tx_path_list,
+    })
+}
+
+pub fn build_tx_root(tx_list: &[BlockTx], height: HeightType) -> Result<Option<TxRootType>, ChunkValidationError> {
+    Ok(build_tx_root_tree(tx_list, height)?.tx_root)
+}
+
+#[derive(PartialEq, Debug)]
+pub struct TxRootCheck {
+    pub tx_root: Option<TxRootType>,
+    pub expected_tx_root: Option<TxRootType>,
+    pub block_size: WeaveSizeType,
+    pub expected_block_size: WeaveSizeType,
+}
+impl TxRootCheck {
+    pub fn is_valid(&self) -> bool {
+        self.tx_root == self.expected_tx_root && self.block_size == self.expected_block_size
+    }
+}
+
+// Rebuilds tx_root and block_size of block at height from local tx list, compares with block index
+pub fn check_block_tx_root(block_index3: &dyn BlockIndex3, height: HeightType, tx_list: &[BlockTx]) -> Result<TxRootCheck, ChunkValidationError> {
+    let block = block_index3.get_by_height_full(height)
+        .ok_or(ChunkValidationError::HeightOutOfRange { height })?;
+    let tree = build_tx_root_tree(tx_list, height)?;
+    Ok(TxRootCheck {
+        tx_root: tree.tx_root,
+        expected_tx_root: block.tx_root,
+        block_size: tree.block_size,
+        expected_block_size: block.block_size,
+    })
+}
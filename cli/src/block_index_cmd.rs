@@ -0,0 +1,111 @@
+use clap::Subcommand;
+use serde_json::json;
+
+use types::*;
+use block_index::*;
+
+use crate::{CmdResult, Output};
+
+#[derive(Subcommand)]
+pub enum BlockIndexCommand {
+    #[command(about = "Fetch /block_index from first peer which answers")]
+    Download {
+        #[arg(long = "peer", required = true, help = "Peer base url, e.g. http://127.0.0.1:1984, repeat for fallback peers")]
+        peer_list: Vec<String>,
+        #[arg(long, help = "Save block index json there")]
+        out: Option<String>,
+        #[arg(long, help = "Reject peers contradicting checkpoints json file, list of {height, indep_hash, weave_size}")]
+        checkpoints: Option<String>,
+        #[arg(long, help = "Print peer, bytes and entries received to stderr")]
+        progress: bool,
+    },
+    #[command(about = "Load and check block index json file, print tip")]
+    Load {
+        path: String,
+    },
+    #[command(about = "Block at height")]
+    LookupHeight {
+        path: String,
+        height: HeightType,
+    },
+    #[command(about = "Block which owns chunk offset")]
+    LookupOffset {
+        path: String,
+        offset: WeaveOffsetType,
+    },
+    #[command(about = "Compare two block index json files, fails if they differ")]
+    Diff {
+        a: String,
+        b: String,
+    },
+}
+
+fn load(path: &str) -> Result<BlockIndex3Json, Box<dyn std::error::Error>> {
+    let mut index = BlockIndex3Json::new();
+    index.load_sync(path).map_err(|err| format!("{}: {}", path, err))?;
+    Ok(index)
+}
+
+fn summary_json(index: &BlockIndex3Json) -> serde_json::Value {
+    let tip_height = index.get_tip_height();
+    json!({
+        "block_count": tip_height.map(|v| v + 1).unwrap_or(0),
+        "tip": tip_height.and_then(|v| height_lookup_json(index, v)),
+    })
+}
+
+pub fn run(command: BlockIndexCommand) -> CmdResult {
+    match command {
+        BlockIndexCommand::Download { peer_list, out, checkpoints, progress } => {
+            let mut index = BlockIndex3Json::new();
+            if let Some(checkpoints) = &checkpoints {
+                let checkpoint_list = CheckpointList::load_sync(checkpoints).map_err(|err| format!("{}: {}", checkpoints, err))?;
+                index.set_checkpoint_list(Some(checkpoint_list));
+            }
+            // on every attempt start and every MiB
+            let mut last_mib = 0;
+            let mut on_progress = |p: &DownloadProgress| {
+                let mib = p.byte_count >> 20;
+                if progress && (p.byte_count == 0 || mib != last_mib) {
+                    eprintln!("{} attempt {}: {} bytes, {} entries", p.peer_url, p.attempt, p.byte_count, p.entry_count);
+                }
+                last_mib = mib;
+            };
+            let config = DownloadConfig::default();
+            let res = tokio::runtime::Runtime::new()?
+                .block_on(index.download_with_progress(&peer_list, &config, &mut on_progress, &CancellationToken::new()))?;
+            if let Some(out) = &out {
+                index.save_sync(out).map_err(|err| format!("{}: {}", out, err))?;
+            }
+            let mut ret = summary_json(&index);
+            ret["out"] = json!(out);
+            ret["peer_url"] = json!(res.peer_url);
+            Ok(Output::Ok(ret))
+        }
+        BlockIndexCommand::Load { path } => {
+            Ok(Output::Ok(summary_json(&load(&path)?)))
+        }
+        BlockIndexCommand::LookupHeight { path, height } => {
+            let index = load(&path)?;
+            // checked before lookup, height near HeightType::MAX overflows index math
+            if index.get_tip_height().is_none_or(|tip_height| height > tip_height) {
+                return Err(format!("height {} is above tip", height).into());
+            }
+            let block = height_lookup_json(&index, height).ok_or_else(|| format!("height {} is above tip", height))?;
+            Ok(Output::Ok(block))
+        }
+        BlockIndexCommand::LookupOffset { path, offset } => {
+            let index = load(&path)?;
+            let block = offset_lookup_json(&index, offset).ok_or_else(|| format!("offset {} is not in block index", offset))?;
+            Ok(Output::Ok(block))
+        }
+        BlockIndexCommand::Diff { a, b } => {
+            let res = diff(&load(&a)?, &load(&b)?);
+            let json = serde_json::to_value(&res)?;
+            if !res.is_same() {
+                return Ok(Output::Fail(json));
+            }
+            Ok(Output::Ok(json))
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::fs;
+use clap::Subcommand;
+use data_encoding::BASE64URL_NOPAD;
+use serde_json::json;
+
+use types::*;
+use block_index::*;
+use chunk::*;
+
+use crate::{CmdResult, Output};
+
+#[derive(Subcommand)]
+pub enum ChunkCommand {
+    #[command(about = "Validate tx_path, data_path and chunk bytes of chunk json (GET /chunk/{offset} answer) at offset")]
+    Validate {
+        chunk: String,
+        offset: WeaveOffsetType,
+        #[arg(long, help = "Block index json file")]
+        block_index: String,
+        #[arg(long, default_value_t = DEFAULT_STRICT_DATA_SPLIT_THRESHOLD)]
+        strict_data_split_threshold: WeaveOffsetType,
+    },
+    #[command(about = "Decode base64url fields of chunk json, split paths into merkle nodes")]
+    Decode {
+        chunk: String,
+        #[arg(long, help = "Write raw chunk bytes there")]
+        out: Option<String>,
+    },
+}
+
+fn load_chunk(path: &str) -> Result<(ChunkJson, Chunk), Box<dyn std::error::Error>> {
+    let chunk_json: ChunkJson = serde_json::from_str(&fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?)?;
+    let chunk = chunk_from_json(&chunk_json)?;
+    Ok((chunk_json, chunk))
+}
+
+// note is 256 bit BE offset, decimal only when it fits offset type, same rule as validation
+fn note_json(note: &[u8]) -> serde_json::Value {
+    match note_to_offset(note) {
+        Some(offset) => json!(offset.to_string()),
+        None => json!(BASE64URL_NOPAD.encode(note)),
+    }
+}
+
+// branch: left | right | note, leaf: data | note
+fn path_json(path: &[u8]) -> serde_json::Value {
+    let branch_size = 2 * CHUNKROOT_LENGTH + NOTE_LENGTH;
+    let leaf_size = CHUNKROOT_LENGTH + NOTE_LENGTH;
+    let mut branch_list = Vec::new();
+    let mut rest = path;
+    while rest.len() > leaf_size && rest.len() >= branch_size {
+        branch_list.push(json!({
+            "left": BASE64URL_NOPAD.encode(&rest[..CHUNKROOT_LENGTH]),
+            "right": BASE64URL_NOPAD.encode(&rest[CHUNKROOT_LENGTH..2 * CHUNKROOT_LENGTH]),
+            "offset": note_json(&rest[2 * CHUNKROOT_LENGTH..branch_size]),
+        }));
+        rest = &rest[branch_size..];
+    }
+    let leaf = if rest.len() == leaf_size {
+        json!({
+            "data": BASE64URL_NOPAD.encode(&rest[..CHUNKROOT_LENGTH]),
+            "offset": note_json(&rest[CHUNKROOT_LENGTH..]),
+        })
+    } else {
+        serde_json::Value::Null
+    };
+    json!({
+        "size": path.len(),
+        "branch_list": branch_list,
+        // null if path is malformed
+        "leaf": leaf,
+    })
+}
+
+pub fn run(command: ChunkCommand) -> CmdResult {
+    match command {
+        ChunkCommand::Validate { chunk, offset, block_index, strict_data_split_threshold } => {
+            let (_, chunk) = load_chunk(&chunk)?;
+            let mut index = BlockIndex3Json::new();
+            index.load_sync(&block_index).map_err(|err| format!("{}: {}", block_index, err))?;
+
+            let res = validate_chunk(&chunk, offset, &index, strict_data_split_threshold);
+            let mut ret = json!({ "valid": res.is_valid() });
+            if let Some(tx_res) = &res.tx_path {
+                ret["data_root"] = json!(BASE64URL_NOPAD.encode(&tx_res.data_root));
+                ret["tx_start"] = json!(tx_res.tx_start.to_string());
+                ret["tx_end"] = json!(tx_res.tx_end.to_string());
+                ret["recall_bucket_offset"] = json!(tx_res.recall_bucket_offset.to_string());
+            }
+            if let Some(data_res) = &res.data_path {
+                ret["data_path_chunk_id"] = json!(BASE64URL_NOPAD.encode(&data_res.chunk_id));
+                ret["chunk_size"] = json!(data_res.chunk_size.to_string());
+                ret["offset_diff"] = json!(data_res.offset_diff.to_string());
+            }
+            if let Some(chunk_id) = &res.chunk_id {
+                ret["chunk_id"] = json!(BASE64URL_NOPAD.encode(chunk_id));
+            }
+            match &res.err {
+                None => Ok(Output::Ok(ret)),
+                Some((stage, err)) => {
+                    ret["stage"] = json!(stage.as_str());
+                    ret["error"] = json!(err.to_string());
+                    Ok(Output::Fail(ret))
+                }
+            }
+        }
+        ChunkCommand::Decode { chunk, out } => {
+            let (chunk_json, chunk) = load_chunk(&chunk)?;
+            if let Some(out) = &out {
+                fs::write(out, &chunk.chunk).map_err(|err| format!("{}: {}", out, err))?;
+            }
+            Ok(Output::Ok(json!({
+                "packing": chunk_json.packing,
+                "chunk_size": chunk.chunk.len(),
+                "tx_path": path_json(&chunk.tx_path),
+                "data_path": path_json(&chunk.data_path),
+                "out": out,
+            })))
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::io::Write;
+use std::process::ExitCode;
+use clap::{Parser, Subcommand};
+
+mod block_index_cmd;
+mod chunk_cmd;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+//  arweave-tools
+//  purpose - one binary for ad-hoc tasks over block_index and chunk crates
+//  every subcommand prints single json document to stdout
+//  on failure it is {"error": "..."} and exit code is 1 (chunk validate adds "valid": false)
+//  block-index diff of different files prints the diff and exits with 1
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Parser)]
+#[command(name = "arweave-tools", version, about = "Arweave block index and chunk tools, json output")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(subcommand, about = "Block index download and lookups")]
+    BlockIndex(block_index_cmd::BlockIndexCommand),
+    #[command(subcommand, about = "Chunk validation and decoding")]
+    Chunk(chunk_cmd::ChunkCommand),
+}
+
+pub enum Output {
+    Ok(serde_json::Value),
+    Fail(serde_json::Value),
+}
+
+pub type CmdResult = Result<Output, Box<dyn std::error::Error>>;
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let res = match cli.command {
+        Command::BlockIndex(command) => block_index_cmd::run(command),
+        Command::Chunk(command) => chunk_cmd::run(command),
+    };
+    let (json, exit_code) = match res {
+        Ok(Output::Ok(json)) => (json, ExitCode::SUCCESS),
+        Ok(Output::Fail(json)) => (json, ExitCode::FAILURE),
+        Err(err) => (serde_json::json!({ "error": err.to_string() }), ExitCode::FAILURE),
+    };
+    // closed stdout (| head) is not an error worth panic
+    let _ = writeln!(std::io::stdout(), "{}", serde_json::to_string_pretty(&json).unwrap());
+    exit_code
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,110 @@
+#[cfg(test)]
+mod cli_test {
+    use crate::*;
+    use crate::block_index_cmd::BlockIndexCommand;
+    use crate::chunk_cmd::ChunkCommand;
+    use serde_json::json;
+
+    const BLOCK_INDEX_PATH: &str = "../test_asset/block_index_slice";
+
+    fn ok(res: CmdResult) -> serde_json::Value {
+        match res.unwrap() {
+            Output::Ok(json) => json,
+            Output::Fail(json) => panic!("unexpected fail {}", json),
+        }
+    }
+
+    fn fail(res: CmdResult) -> serde_json::Value {
+        match res.unwrap() {
+            Output::Ok(json) => panic!("unexpected ok {}", json),
+            Output::Fail(json) => json,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let cli = Cli::try_parse_from(["arweave-tools", "chunk", "validate", "c.json", "599059", "--block-index", "bi"]).unwrap();
+        assert!(matches!(cli.command, Command::Chunk(ChunkCommand::Validate { offset: 599059, strict_data_split_threshold: types::DEFAULT_STRICT_DATA_SPLIT_THRESHOLD, .. })));
+        let cli = Cli::try_parse_from(["arweave-tools", "block-index", "download", "--peer", "a", "--peer", "b"]).unwrap();
+        assert!(matches!(cli.command, Command::BlockIndex(BlockIndexCommand::Download { ref peer_list, .. }) if peer_list.len() == 2));
+        let cli = Cli::try_parse_from(["arweave-tools", "block-index", "download", "--peer", "a", "--checkpoints", "cp.json"]).unwrap();
+        assert!(matches!(cli.command, Command::BlockIndex(BlockIndexCommand::Download { checkpoints: Some(ref path), .. }) if path == "cp.json"));
+        assert!(Cli::try_parse_from(["arweave-tools", "block-index", "download"]).is_err());
+        assert!(Cli::try_parse_from(["arweave-tools", "block-index", "lookup-height", "bi", "-1"]).is_err());
+        let cli = Cli::try_parse_from(["arweave-tools", "block-index", "diff", "a.json", "b.json"]).unwrap();
+        assert!(matches!(cli.command, Command::BlockIndex(BlockIndexCommand::Diff { ref a, ref b }) if a == "a.json" && b == "b.json"));
+    }
+
+    #[test]
+    fn test_block_index() {
+        let res = ok(block_index_cmd::run(BlockIndexCommand::Load { path: BLOCK_INDEX_PATH.into() }));
+        assert_eq!(res["block_count"], 4308);
+        assert_eq!(res["tip"]["height"], 4307);
+
+        let res = ok(block_index_cmd::run(BlockIndexCommand::LookupHeight { path: BLOCK_INDEX_PATH.into(), height: 82 }));
+        assert_eq!(res, json!({
+            "height": 82,
+            "indep_hash": "6OAy50Jx7O7JxHkG8SbGenvX_aHQ-6klsc7gOhLtDF1ebleir2sSJ1_MI3VKSv7N",
+            "weave_size": "599058",
+            "tx_root": "MzrD8OItolyWnLw9YOheDsAxO5tJeSLAy5QbCYrNJR8",
+        }));
+        let res = ok(block_index_cmd::run(BlockIndexCommand::LookupOffset { path: BLOCK_INDEX_PATH.into(), offset: 599059 }));
+        assert_eq!(res["height"], 3527);
+        assert_eq!(res["block_start"], "599058");
+
+        assert!(block_index_cmd::run(BlockIndexCommand::LookupHeight { path: BLOCK_INDEX_PATH.into(), height: 4308 }).is_err());
+        assert!(block_index_cmd::run(BlockIndexCommand::LookupHeight { path: BLOCK_INDEX_PATH.into(), height: u64::MAX }).is_err());
+        assert!(block_index_cmd::run(BlockIndexCommand::LookupOffset { path: BLOCK_INDEX_PATH.into(), offset: 1039030 }).is_err());
+        assert!(block_index_cmd::run(BlockIndexCommand::Load { path: "../test_asset/nope".into() }).is_err());
+
+        let res = ok(block_index_cmd::run(BlockIndexCommand::Diff { a: BLOCK_INDEX_PATH.into(), b: BLOCK_INDEX_PATH.into() }));
+        assert_eq!(res["a_tip_height"], 4307);
+        assert_eq!(res["first_diverging_height"], json!(null));
+        // without 8 newest blocks
+        let block_list: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(BLOCK_INDEX_PATH).unwrap()).unwrap();
+        let short_path = std::env::temp_dir().join("cli_block_index_short");
+        std::fs::write(&short_path, serde_json::to_string(&block_list[8..]).unwrap()).unwrap();
+        let res = fail(block_index_cmd::run(BlockIndexCommand::Diff { a: BLOCK_INDEX_PATH.into(), b: short_path.to_str().unwrap().into() }));
+        assert_eq!((res["a_tip_height"].clone(), res["b_tip_height"].clone()), (json!(4307), json!(4299)));
+        assert!(block_index_cmd::run(BlockIndexCommand::Diff { a: BLOCK_INDEX_PATH.into(), b: "../test_asset/nope".into() }).is_err());
+        std::fs::remove_file(&short_path).unwrap();
+    }
+
+    #[test]
+    fn test_chunk() {
+        let validate = |offset| chunk_cmd::run(ChunkCommand::Validate {
+            chunk: "../test_asset/chunk_599059.json".into(),
+            offset,
+            block_index: BLOCK_INDEX_PATH.into(),
+            strict_data_split_threshold: types::DEFAULT_STRICT_DATA_SPLIT_THRESHOLD,
+        });
+        let res = ok(validate(599059));
+        assert_eq!(res["valid"], true);
+        assert_eq!(res["data_root"], "nyGPB30FMq2Bx7TRNXInl6rKFSN4W5na9RycpGbT5IA");
+        assert_eq!(res["tx_end"], "439971");
+        assert_eq!(res["chunk_size"], "262144");
+        assert_eq!(res["chunk_id"], "wslYzrJb_EL8ebZipBv1sb-naKznJoA87aEEr_G_2_g");
+        assert_eq!(res["chunk_id"], res["data_path_chunk_id"]);
+
+        // chunk of other block
+        let res = fail(validate(1));
+        assert_eq!(res["valid"], false);
+        assert_eq!(res["stage"], "tx_path");
+        let res = fail(validate(1039030));
+        assert_eq!(res["error"], "chunk offset 1039030 is not in block index");
+
+        let out = std::env::temp_dir().join("cli_chunk_1_raw");
+        let res = ok(chunk_cmd::run(ChunkCommand::Decode {
+            chunk: "../test_asset/chunk_1.json".into(),
+            out: Some(out.to_str().unwrap().into()),
+        }));
+        assert_eq!(res["packing"], "unpacked");
+        assert_eq!(res["chunk_size"], 262144);
+        assert_eq!(std::fs::metadata(&out).unwrap().len(), 262144);
+        assert_eq!(res["tx_path"]["branch_list"], json!([]));
+        assert_eq!(res["tx_path"]["leaf"]["data"], "kuMLOSJKG7O4NmSBY9KZ2PjU-5O4UBNFl_-kF9FnW7w");
+        assert_eq!(res["tx_path"]["leaf"]["offset"], "599058");
+        assert_eq!(res["data_path"]["branch_list"][1]["offset"], "262144");
+        std::fs::remove_file(&out).unwrap();
+    }
+}
@@ -30,9 +30,24 @@ pub struct BlockIndexEntity {
     pub block_size: WeaveSizeType,
 }
 
+// Block which owns chunk offset, see BlockIndex3::locate_offset
+#[derive(PartialEq, Debug)]
+pub struct BlockLocation {
+    pub height: HeightType,
+    // chunk offsets in (block_start, block_end] belong to block
+    // block_start is weave_size of previous block (0 for genesis), block_end is weave_size of block
+    pub block_start: WeaveOffsetType,
+    pub block_end: WeaveOffsetType,
+    pub block_size: WeaveSizeType,
+    pub tx_root: Option<TxRootType>,
+}
+
 pub trait BlockIndex {}
 
 pub trait BlockIndex3: BlockIndex {
+    // None for empty index
+    fn get_tip_height(&self) -> Option<HeightType>;
+
     fn get_by_height_full(&self, height: HeightType) -> Option<BlockIndexEntity>;
     fn get_by_height_indep_hash(&self, height: HeightType) -> Option<IndepHashType>;
     fn get_by_height_weave_size(&self, height: HeightType) -> Option<WeaveSizeType>;
@@ -48,4 +63,18 @@ pub trait BlockIndex3: BlockIndex {
     fn get_by_chunk_offset_indep_hash_orig(&self, chunk_offset: WeaveOffsetType) -> Option<String>;
     fn get_by_chunk_offset_weave_size_orig(&self, chunk_offset: WeaveOffsetType) -> Option<String>;
     fn get_by_chunk_offset_tx_root_orig(&self, chunk_offset: WeaveOffsetType) -> Option<String>;
+
+    // Lowest height with weave_size >= chunk_offset, None outside [genesis weave_size, tip weave_size]
+    // Empty blocks (no txs) repeat weave_size of previous block and own no offsets,
+    // so chunk offset in run of equal weave_size always resolves to first block of run, the one with data
+    // e.g. in test slice heights 3528..=4307 have same weave_size as 3527, offsets there resolve to 3527
+    // Only exception is chunk_offset == genesis weave_size, it resolves to genesis with block_size 0
+    fn locate_offset(&self, chunk_offset: WeaveOffsetType) -> Option<BlockLocation>;
+}
+
+// Lookups by value, backed by maps built on load
+pub trait BlockIndex3Reverse: BlockIndex3 {
+    fn get_height_by_indep_hash(&self, indep_hash: &IndepHashType) -> Option<HeightType>;
+    // oldest first, empty if tx_root is unknown; blocks without txs are not indexed
+    fn get_heights_by_tx_root(&self, tx_root: &TxRootType) -> Vec<HeightType>;
 }